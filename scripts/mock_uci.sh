@@ -0,0 +1,17 @@
+#!/bin/sh
+# A stand-in for a UCI engine in the tests, so no real engine has to be installed.
+# It answers every go with the move given as its first argument, or never answers when that is "sleep".
+move="$1"
+
+while read -r line; do
+    case "$line" in
+        uci) echo "id name mock"; echo "id author fishermann"; echo "uciok" ;;
+        isready) echo "readyok" ;;
+        go*)
+            if [ "$move" != "sleep" ]; then
+                echo "info depth 1 score cp 0 pv $move"
+                echo "bestmove $move"
+            fi ;;
+        quit) exit 0 ;;
+    esac
+done
@@ -39,7 +39,8 @@ while True:
     cur_nodes = int(cur_nodes / 4)
 
     if cur_nodes <= 1:
-        model.add(tf.keras.layers.Dense(cur_nodes, activation='relu'))
+        # a linear output, as a ReLU would never give black the advantage
+        model.add(tf.keras.layers.Dense(cur_nodes, activation=None))
         break
 
 # create a loss function
@@ -54,4 +55,13 @@ model.fit(x_train_data, y_train_data, validation_data=(x_test_data, y_test_data)
 # evaluate how well we did with test data
 model.evaluate(x_test_data, y_test_data, verbose=1)
 
+# save the model for the engine: the layer sizes, then each layer's weights and biases on a line each
+# load it with: fishermann --evaluator nn data/nn.weights
+with open('../data/nn.weights', 'w') as fp:
+    fp.write(' '.join(str(size) for size in [x_data_width] + [layer.units for layer in model.layers]) + '\n')
 
+    for layer in model.layers:
+        kernel, bias = layer.get_weights()
+
+        fp.write(' '.join(str(w) for w in kernel.flatten()) + '\n')
+        fp.write(' '.join(str(b) for b in bias) + '\n')
@@ -0,0 +1,210 @@
+use std::fs::File;
+use std::fmt;
+use std::io::{BufRead, BufReader};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::Instant;
+
+use shakmaty::{Chess, CastlingMode};
+use shakmaty::fen::Fen;
+
+use crate::evaluator::PstEvaluator;
+use crate::parallel::parallel_negamax;
+use crate::result::SearchResult;
+use crate::score::Score;
+use crate::search::{SearchContext, SearchLimits, SearchOptions, Algorithm, iterative_deepening, search};
+use crate::tt::TranspositionTable;
+
+/// Scores are clamped to this, so mates don't swamp the error
+const MAX_SCORE :i64 = 3000;
+
+const HASH_MB :usize = 16;
+
+/// The searches of every position with one configuration
+struct Run<T> {
+    /// What the search returned for each position
+    results: Vec<T>,
+    nodes: u64,
+    seconds: f64,
+}
+
+impl<T> fmt::Display for Run<T> {
+    fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NODES: {}\t{:.2}s\tNPS: {:.0}", self.nodes, self.seconds, self.nodes as f64 / self.seconds)
+    }
+}
+
+/// Searches each position in turn with the options, sharing a table between them like a game would
+fn search_positions<T, F>(positions :&[(i64, Chess)], options :&SearchOptions, mut search :F) -> Run<T>
+    where F: FnMut(&mut SearchContext<PstEvaluator>, &Chess) -> T
+{
+    let tt = TranspositionTable::new(HASH_MB);
+    let eval = PstEvaluator::default();
+
+    let start = Instant::now();
+    let mut nodes = 0;
+
+    let results = positions.iter().map(|(_, game)| {
+        tt.new_search();
+
+        let mut ctx = SearchContext::new(Arc::new(AtomicBool::new(false)), &tt, &eval, options.clone());
+        let result = search(&mut ctx, game);
+
+        nodes += ctx.nodes;
+        result
+    }).collect();
+
+    Run { results, nodes, seconds: start.elapsed().as_secs_f64() }
+}
+
+/// How far off the score is from the label, with both clamped so mates don't swamp the error
+fn error(score :Score, label :i64) -> i64 {
+    ((score.raw() as i64).clamp(-MAX_SCORE, MAX_SCORE) - label.clamp(-MAX_SCORE, MAX_SCORE)).abs()
+}
+
+/// Reads a file of "score: epd" lines, like data/rand_gen_10k.fen
+pub fn read_labeled_fens(path :&str) -> Vec<(i64, Chess)> {
+    let file = BufReader::new(File::open(path).expect("Error opening FEN file"));
+
+    file.lines().filter_map(|line| {
+        let line = line.expect("Error reading FEN file");
+        let (score, epd) = line.split_once(':')?;
+
+        let score = score.trim().parse::<i64>().ok()?;
+        let setup :Fen = epd.trim().parse().ok()?;
+        let game = setup.position(CastlingMode::Standard).ok()?;
+
+        Some((score, game))
+    }).collect()
+}
+
+/// Searches every labeled position with and without quiescence, and reports how far off our scores are
+pub fn quiescence(path :&str, depth :usize, count :usize) {
+    let positions = read_labeled_fens(path);
+    let positions = &positions[..count.min(positions.len())];
+    let limits = SearchLimits { depth: Some(depth), ..SearchLimits::default() };
+
+    println!("POSITIONS: {} DEPTH: {}", positions.len(), depth);
+
+    for quiescence in [false, true] {
+        let options = SearchOptions { quiescence, ..SearchOptions::default() };
+        let run = search_positions(positions, &options, |ctx, game| iterative_deepening(ctx, Algorithm::AlphaBeta, game, &limits, |_, _, _, _| ()).0);
+
+        let labels = positions.iter().map(|(label, _)| *label);
+        let total_error = run.results.iter().zip(labels.clone()).map(|(score, label)| error(*score, label)).sum::<i64>();
+        let same_sign = run.results.iter().zip(labels).filter(|(score, label)| (score.raw() as i64).signum() == label.signum()).count();
+
+        println!("QUIESCENCE: {}\t{}\tMAE: {:.1}\tSAME SIGN: {:.1}%",
+                 quiescence,
+                 run,
+                 total_error as f64 / positions.len() as f64,
+                 same_sign as f64 * 100.0 / positions.len() as f64);
+    }
+}
+
+/// Searches every position to a fixed depth with each algorithm, comparing nodes, time, and scores
+pub fn algorithms(path :&str, depth :usize, count :usize) {
+    let positions = read_labeled_fens(path);
+    let positions = &positions[..count.min(positions.len())];
+    let mut first_scores = Vec::new();
+
+    // pruning changes the scores, and basic doesn't prune, so turn it off to compare like with like
+    let options = SearchOptions { null_move: false, lmr: false, delta_pruning: false, ..SearchOptions::default() };
+
+    println!("POSITIONS: {} DEPTH: {}", positions.len(), depth);
+
+    for algorithm in Algorithm::ALL.iter() {
+        let run = search_positions(positions, &options, |ctx, game| search(ctx, *algorithm, game, depth).0);
+
+        // every algorithm should agree with the first one, they just get there with fewer nodes
+        if first_scores.is_empty() {
+            first_scores = run.results.clone();
+        }
+
+        let same = run.results.iter().zip(first_scores.iter()).filter(|(a, b)| a == b).count();
+
+        println!("{}:\t{}\tSAME SCORE: {}/{}", algorithm.name(), run, same, positions.len());
+    }
+}
+
+/// Searches every position with each algorithm and prints the full result of each search, one per line,
+/// as text or as JSON objects so they can be loaded into something else to compare
+pub fn results(path :&str, depth :usize, count :usize, json :bool) {
+    let positions = read_labeled_fens(path);
+    let positions = &positions[..count.min(positions.len())];
+    let limits = SearchLimits { depth: Some(depth), ..SearchLimits::default() };
+    let eval = PstEvaluator::default();
+
+    for (i, (_, game)) in positions.iter().enumerate() {
+        for algorithm in Algorithm::ALL.iter() {
+            let tt = TranspositionTable::new(HASH_MB);
+            let mut ctx = SearchContext::new(Arc::new(AtomicBool::new(false)), &tt, &eval, SearchOptions::default());
+            let result = SearchResult::search(&mut ctx, 1, *algorithm, game, &limits);
+
+            if json {
+                println!("{{\"position\":{},\"algorithm\":\"{}\",\"result\":{}}}", i, algorithm.name(), result.to_json());
+            } else {
+                println!("{} {}: {}", i, algorithm.name(), result.to_text());
+            }
+        }
+    }
+}
+
+/// Searches every position to a fixed depth with null move pruning and late move reductions turned on and off,
+/// comparing the nodes searched and how often the best move changes because of the pruning
+pub fn pruning(path :&str, depth :usize, count :usize) {
+    let positions = read_labeled_fens(path);
+    let positions = &positions[..count.min(positions.len())];
+    let limits = SearchLimits { depth: Some(depth), ..SearchLimits::default() };
+    let mut first_moves = Vec::new();
+
+    println!("POSITIONS: {} DEPTH: {}", positions.len(), depth);
+
+    for (null_move, lmr) in [(false, false), (true, false), (false, true), (true, true)] {
+        let options = SearchOptions { null_move, lmr, ..SearchOptions::default() };
+        let run = search_positions(positions, &options, |ctx, game| {
+            let (score, moves) = iterative_deepening(ctx, Algorithm::AlphaBeta, game, &limits, |_, _, _, _| ());
+
+            (score, moves.last().cloned())
+        });
+
+        let total_error = run.results.iter().zip(positions).map(|((score, _), (label, _))| error(*score, *label)).sum::<i64>();
+        let best_moves = run.results.iter().map(|(_, mv)| mv.clone()).collect::<Vec<_>>();
+
+        // compare against searching without either
+        if first_moves.is_empty() {
+            first_moves = best_moves.clone();
+        }
+
+        let same = best_moves.iter().zip(first_moves.iter()).filter(|(a, b)| a == b).count();
+
+        println!("NULL MOVE: {}\tLMR: {}\t{}\tMAE: {:.1}\tSAME MOVE: {}/{}",
+                 null_move,
+                 lmr,
+                 run,
+                 total_error as f64 / positions.len() as f64,
+                 same,
+                 positions.len());
+    }
+}
+
+/// Searches every position to a fixed depth with 1, 2, 4, ... threads, to see how much the extra threads help
+pub fn threads(path :&str, depth :usize, count :usize, max_threads :usize) {
+    let positions = read_labeled_fens(path);
+    let positions = &positions[..count.min(positions.len())];
+    let limits = SearchLimits { depth: Some(depth), ..SearchLimits::default() };
+    let mut single_thread_time = None;
+
+    println!("POSITIONS: {} DEPTH: {}", positions.len(), depth);
+
+    let thread_counts = (0..).map(|i| 1 << i).take_while(|t| *t < max_threads).chain([max_threads]);
+
+    for threads in thread_counts {
+        let run = search_positions(positions, &SearchOptions::default(), |ctx, game| {
+            parallel_negamax(ctx, threads, Algorithm::AlphaBeta, game, &limits, |_, _, _, _| ());
+        });
+        let single_thread_time = *single_thread_time.get_or_insert(run.seconds);
+
+        println!("THREADS: {}\t{}\tSPEEDUP: {:.2}x", threads, run, single_thread_time / run.seconds);
+    }
+}
@@ -0,0 +1,272 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+use shakmaty::{Chess, CastlingMode};
+use shakmaty::fen::Fen;
+
+use crate::eval::BUILTIN_WEIGHTS;
+use crate::evaluator::{Evaluator, EVALUATORS, load_evaluator};
+use crate::result::SearchResult;
+use crate::search::{SearchContext, SearchLimits, SearchOptions, Algorithm};
+use crate::tablebase::Tablebase;
+use crate::time::TimeBudget;
+use crate::tt::TranspositionTable;
+use crate::uci::UciEngine;
+use crate::weights::Weights;
+
+/// Searched when neither a depth nor a move time is given
+const DEFAULT_DEPTH :usize = 7;
+const DEFAULT_HASH_MB :usize = 16;
+
+pub const USAGE :&str = "[--fen <fen> | --batch <fen file>] [--depth N] [--movetime ms] [--algorithm basic|ab|pvs] [--threads N] \
+[--hash MB] [--eval <weights file>] [--evaluator pst|linear|nn [weights file]] [--syzygy <tablebase directories>] [--output text|json]";
+
+/// The options of a search from the command line, without --fen or --batch we speak UCI instead
+#[derive(Debug, Clone, PartialEq)]
+pub struct CliOptions {
+    pub fen: Option<String>,
+    /// A file with a FEN or EPD per line, each one is searched and gets a line of output
+    pub batch: Option<String>,
+    pub depth: Option<usize>,
+    pub movetime: Option<u64>,
+    pub algorithm: Algorithm,
+    pub threads: usize,
+    pub hash_mb: usize,
+    /// Weights for the hand written evaluator
+    pub eval: Option<String>,
+    pub evaluator: String,
+    /// Weights for the learned evaluators
+    pub evaluator_file: Option<String>,
+    /// The directories of the Syzygy tables, separated like PATH
+    pub syzygy: Option<String>,
+    pub json: bool,
+}
+
+impl Default for CliOptions {
+    fn default() -> Self {
+        CliOptions {
+            fen: None,
+            batch: None,
+            depth: None,
+            movetime: None,
+            algorithm: Algorithm::default(),
+            threads: 1,
+            hash_mb: DEFAULT_HASH_MB,
+            eval: None,
+            evaluator: EVALUATORS[0].to_string(),
+            evaluator_file: None,
+            syzygy: None,
+            json: false
+        }
+    }
+}
+
+impl CliOptions {
+    /// Parses the arguments after the program name
+    pub fn parse(args :&[String]) -> Result<CliOptions, String> {
+        let mut options = CliOptions::default();
+        let mut args = args.iter().peekable();
+
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().map(|v| v.to_string()).ok_or(format!("Missing value for {}", flag));
+            let number = |v :String| v.parse::<usize>().map_err(|_| format!("Invalid number for {}: {}", flag, v));
+
+            match flag.as_str() {
+                "--fen" => options.fen = Some(value()?),
+                "--batch" => options.batch = Some(value()?),
+                "--depth" => options.depth = Some(number(value()?)?),
+                "--movetime" => options.movetime = Some(number(value()?)? as u64),
+                "--algorithm" => {
+                    let name = value()?;
+                    options.algorithm = Algorithm::from_name(&name).ok_or(format!("Unknown algorithm: {}", name))?;
+                },
+                "--threads" => options.threads = number(value()?)?.max(1),
+                "--hash" => options.hash_mb = number(value()?)?,
+                "--eval" => options.eval = Some(value()?),
+                "--evaluator" => {
+                    options.evaluator = value()?;
+
+                    // the learned evaluators' weights file can follow the name
+                    if args.peek().is_some_and(|next| !next.starts_with("--")) {
+                        options.evaluator_file = args.next().cloned();
+                    }
+                },
+                "--syzygy" => options.syzygy = Some(value()?),
+                "--output" => options.json = match value()?.as_str() {
+                    "text" => false,
+                    "json" => true,
+                    other => return Err(format!("Unknown output format: {}", other))
+                },
+                _ => return Err(format!("Unknown option: {}", flag))
+            }
+        }
+
+        Ok(options)
+    }
+
+    /// The evaluator with its weights, as chosen by --evaluator and --eval
+    pub fn load_evaluator(&self) -> Result<Arc<dyn Evaluator>, String> {
+        let weights = match &self.eval {
+            Some(path) => Weights::load(path, &BUILTIN_WEIGHTS)?,
+            None => BUILTIN_WEIGHTS
+        };
+
+        load_evaluator(&self.evaluator, self.evaluator_file.as_deref(), &weights)
+    }
+
+    pub fn limits(&self) -> SearchLimits {
+        SearchLimits {
+            depth: self.depth.or(if self.movetime.is_none() { Some(DEFAULT_DEPTH) } else { None }),
+            time: self.movetime.map(|ms| TimeBudget::MoveTime(Duration::from_millis(ms))),
+            ..SearchLimits::default()
+        }
+    }
+}
+
+/// Searches the FEN or every position in the batch file, or runs the UCI engine when there are neither
+pub fn run(options :&CliOptions) -> Result<(), String> {
+    if options.fen.is_none() && options.batch.is_none() {
+        return uci_engine(options).map(|mut engine| engine.run());
+    }
+
+    let eval = options.load_evaluator()?;
+    let tt = TranspositionTable::new(options.hash_mb);
+    let limits = options.limits();
+    let tablebase = options.syzygy.as_deref().map(Tablebase::open).transpose()?;
+
+    let fens = match (&options.fen, &options.batch) {
+        (Some(fen), _) => vec![fen.clone()],
+        (_, Some(path)) => {
+            let file = BufReader::new(File::open(path).map_err(|e| format!("Error opening {}: {}", path, e))?);
+
+            file.lines()
+                .map(|line| line.map_err(|e| format!("Error reading {}: {}", path, e)))
+                .collect::<Result<Vec<_>, _>>()?
+                .into_iter()
+                .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+                .collect()
+        },
+        _ => unreachable!()
+    };
+
+    for fen in fens {
+        let game = match parse_position(&fen) {
+            Ok(game) => game,
+            Err(e) => { eprintln!("{}", e); continue; }
+        };
+
+        // every position gets a fresh table, so the results don't depend on the order of the batch
+        tt.clear();
+        tt.new_search();
+
+        let mut ctx = SearchContext::new(Arc::new(AtomicBool::new(false)), &tt, &eval, SearchOptions::default());
+        ctx.set_tablebase(tablebase.as_ref());
+
+        let result = SearchResult::search(&mut ctx, options.threads, options.algorithm, &game, &limits);
+        let fen = fen.trim();
+
+        if options.json {
+            println!("{{\"fen\":\"{}\",\"algorithm\":\"{}\",\"result\":{}}}", fen, options.algorithm.name(), result.to_json());
+        } else {
+            println!("{} | {} | {}", fen, options.algorithm.name(), result.to_text());
+        }
+    }
+
+    Ok(())
+}
+
+/// A UCI engine with the options applied, the GUI sets the limits of each search so those are rejected
+fn uci_engine(options :&CliOptions) -> Result<UciEngine, String> {
+    if options.depth.is_some() || options.movetime.is_some() || options.json {
+        return Err("--depth, --movetime and --output only apply with --fen or --batch".to_string());
+    }
+
+    let mut engine = UciEngine::new();
+
+    engine.set_algorithm(options.algorithm);
+    engine.set_threads(options.threads)?;
+    engine.set_hash(options.hash_mb)?;
+    engine.set_eval_file(options.eval.as_deref())?;
+    engine.set_evaluator(&options.evaluator, options.evaluator_file.as_deref())?;
+    engine.set_tablebase(options.syzygy.as_deref())?;
+
+    Ok(engine)
+}
+
+/// A FEN, or just its first 4 fields like in an EPD, anything after those is ignored
+pub fn parse_position(line :&str) -> Result<Chess, String> {
+    let fields = line.split_whitespace().collect::<Vec<_>>();
+    let counters = fields.len() >= 6 && fields[4..6].iter().all(|f| f.parse::<u32>().is_ok());
+
+    let fen = if counters {
+        fields[..6].join(" ")
+    } else if fields.len() >= 4 {
+        format!("{} 0 1", fields[..4].join(" "))
+    } else {
+        return Err(format!("Invalid FEN: {}", line));
+    };
+
+    let setup :Fen = fen.parse().map_err(|e| format!("Invalid FEN {}: {}", line, e))?;
+
+    setup.position(CastlingMode::Standard).map_err(|e| format!("Illegal position {}: {}", line, e))
+}
+
+
+#[cfg(test)]
+mod cli_tests {
+    use crate::cli::{CliOptions, parse_position, uci_engine};
+    use crate::search::Algorithm;
+
+    fn args(line :&str) -> Vec<String> {
+        line.split_whitespace().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_test() {
+        let options = CliOptions::parse(&args("--fen 8/8/8/8/8/8/8/8 --depth 5 --algorithm ab --threads 4 --output json --evaluator nn nn.weights")).unwrap();
+
+        assert_eq!(options.fen.as_deref(), Some("8/8/8/8/8/8/8/8"));
+        assert_eq!(options.limits().depth, Some(5));
+        assert_eq!(options.algorithm, Algorithm::AlphaBeta);
+        assert_eq!((options.threads, options.json), (4, true));
+        assert_eq!((options.evaluator.as_str(), options.evaluator_file.as_deref()), ("nn", Some("nn.weights")));
+
+        // a move time replaces the default depth
+        let options = CliOptions::parse(&args("--movetime 100 --evaluator linear --eval my.weights")).unwrap();
+
+        assert_eq!(options.limits().depth, None);
+        assert!(options.limits().time.is_some());
+        assert_eq!((options.evaluator_file, options.eval.as_deref()), (None, Some("my.weights")));
+        assert_eq!(CliOptions::parse(&[]).unwrap().limits().depth, Some(7));
+
+        assert!(CliOptions::parse(&args("--depth")).is_err());
+        assert!(CliOptions::parse(&args("--depth x")).is_err());
+        assert!(CliOptions::parse(&args("--algorithm mcts")).is_err());
+        assert!(CliOptions::parse(&args("--output xml")).is_err());
+        assert!(CliOptions::parse(&args("--ponder")).is_err());
+        assert_eq!(CliOptions::parse(&args("--syzygy a:b")).unwrap().syzygy.as_deref(), Some("a:b"));
+        assert_eq!(CliOptions::default().algorithm, Algorithm::Pvs);
+
+        assert!(parse_position("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").is_ok());
+        assert!(parse_position("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - bm e4;").is_ok());
+        assert!(parse_position("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w").is_err());
+    }
+
+    #[test]
+    fn uci_engine_test() {
+        let engine = |line :&str| uci_engine(&CliOptions::parse(&args(line)).unwrap());
+
+        assert!(engine("--algorithm ab --threads 2 --hash 1 --evaluator pst").is_ok());
+        assert!(engine(concat!("--syzygy ", env!("CARGO_MANIFEST_DIR"), "/data/syzygy")).is_ok());
+
+        // the GUI sets the limits, and the rest have to be valid
+        assert!(engine("--depth 5").is_err());
+        assert!(engine("--movetime 100").is_err());
+        assert!(engine("--threads 1000").is_err());
+        assert!(engine("--hash 100000").is_err());
+        assert!(engine("--syzygy /does/not/exist").is_err());
+    }
+}
@@ -0,0 +1,206 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::Instant;
+
+use shakmaty::{Chess, Move, CastlingMode};
+use shakmaty::fen::Fen;
+use shakmaty::san::{San, SanPlus};
+use shakmaty::uci::Uci;
+
+use crate::cli::CliOptions;
+use crate::evaluator::Evaluator;
+use crate::result::SearchResult;
+use crate::search::{SearchContext, SearchLimits, SearchOptions, Algorithm};
+use crate::tt::TranspositionTable;
+
+/// Searched when neither a depth nor a move time is given
+const DEFAULT_DEPTH :usize = 6;
+
+pub const USAGE :&str = "<epd file> [--depth N] [--movetime ms] [--algorithm basic|ab|pvs] [--threads N] [--hash MB] \
+[--eval <weights file>] [--evaluator pst|linear|nn [weights file]]";
+
+/// A test position: the moves the engine should play (bm) and the ones it should avoid (am)
+#[derive(Debug, Clone)]
+pub struct EpdPosition {
+    pub id: String,
+    pub game: Chess,
+    pub best_moves: Vec<Move>,
+    pub avoid_moves: Vec<Move>,
+}
+
+impl EpdPosition {
+    /// Passes when the move is one of the best moves, if there are any, and none of the moves to avoid
+    pub fn passes(&self, mv :&Move) -> bool {
+        (self.best_moves.is_empty() || self.best_moves.contains(mv)) && !self.avoid_moves.contains(mv)
+    }
+}
+
+/// Parses a line like: 2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";
+/// Moves are in SAN, though UCI notation is accepted too, and other opcodes are ignored
+pub fn parse_epd(line :&str) -> Result<EpdPosition, String> {
+    let fields = line.split_whitespace().collect::<Vec<_>>();
+
+    if fields.len() < 4 {
+        return Err(format!("Not an EPD line: {}", line));
+    }
+
+    let fen = format!("{} 0 1", fields[..4].join(" "));
+    let setup :Fen = fen.parse().map_err(|e| format!("Invalid EPD {}: {}", line, e))?;
+    let game :Chess = setup.position(CastlingMode::Standard).map_err(|e| format!("Illegal position {}: {}", line, e))?;
+
+    // the operations start after the 4 FEN fields
+    let mut rest = line.trim_start();
+
+    for _ in 0..4 {
+        rest = rest[rest.find(char::is_whitespace).unwrap_or(rest.len())..].trim_start();
+    }
+
+    let mut position = EpdPosition { id: String::new(), game, best_moves: Vec::new(), avoid_moves: Vec::new() };
+
+    for operation in rest.split(';').map(|op| op.trim()).filter(|op| !op.is_empty()) {
+        let (opcode, operands) = operation.split_once(char::is_whitespace).unwrap_or((operation, ""));
+
+        match opcode {
+            "bm" => position.best_moves = parse_moves(&position.game, operands)?,
+            "am" => position.avoid_moves = parse_moves(&position.game, operands)?,
+            "id" => position.id = operands.trim().trim_matches('"').to_string(),
+            _ => ()
+        }
+    }
+
+    Ok(position)
+}
+
+fn parse_moves(game :&Chess, moves :&str) -> Result<Vec<Move>, String> {
+    moves.split_whitespace().map(|mv| {
+        let san = SanPlus::from_ascii(mv.as_bytes()).ok().and_then(|san| san.san.to_move(game).ok());
+
+        san.or_else(|| mv.parse::<Uci>().ok().and_then(|uci| uci.to_move(game).ok()))
+            .ok_or(format!("Illegal move: {}", mv))
+    }).collect()
+}
+
+/// Searches a test position with a fresh table
+pub fn solve<E :Evaluator>(position :&EpdPosition, algorithm :Algorithm, limits :&SearchLimits, threads :usize, tt :&TranspositionTable, eval :&E) -> SearchResult {
+    tt.clear();
+    tt.new_search();
+
+    let mut ctx = SearchContext::new(Arc::new(AtomicBool::new(false)), tt, eval, SearchOptions::default());
+
+    SearchResult::search(&mut ctx, threads, algorithm, &position.game, limits)
+}
+
+/// Runs every position of an EPD file with the search options of the command line, printing whether each one passed and the total
+pub fn run(path :&str, options :&CliOptions) -> Result<(), String> {
+    if options.fen.is_some() || options.batch.is_some() || options.json {
+        return Err("--fen, --batch and --output don't apply to an EPD file".to_string());
+    }
+
+    let file = BufReader::new(File::open(path).map_err(|e| format!("Error opening {}: {}", path, e))?);
+    let eval = options.load_evaluator()?;
+    let tt = TranspositionTable::new(options.hash_mb);
+    let (algorithm, threads) = (options.algorithm, options.threads);
+    let mut limits = options.limits();
+
+    if options.depth.is_none() && options.movetime.is_none() {
+        limits.depth = Some(DEFAULT_DEPTH);
+    }
+
+    let mut passed = 0;
+    let mut total = 0;
+    let mut total_nodes = 0;
+    let start = Instant::now();
+
+    println!("{:<16} {:>6} {:>8} {:>16} {:>10} {:>5} {:>10} {:>8}", "ID", "RESULT", "MOVE", "EXPECTED", "SCORE", "DEPTH", "NODES", "TIME");
+
+    for (line_num, line) in file.lines().enumerate() {
+        let line = line.map_err(|e| format!("Error reading {}: {}", path, e))?;
+
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let position = match parse_epd(&line) {
+            Ok(position) if position.best_moves.is_empty() && position.avoid_moves.is_empty() => {
+                eprintln!("Line {}: no bm or am, skipping", line_num + 1);
+                continue;
+            },
+            Ok(position) => position,
+            Err(e) => {
+                eprintln!("Line {}: {}", line_num + 1, e);
+                continue;
+            }
+        };
+
+        let result = solve(&position, algorithm, &limits, threads, &tt, &eval);
+        let pass = result.best_move().is_some_and(|mv| position.passes(mv));
+
+        total += 1;
+        total_nodes += result.nodes;
+
+        if pass {
+            passed += 1;
+        }
+
+        let san = |mv :&Move| San::from_move(&position.game, mv).to_string();
+        let expected = if position.best_moves.is_empty() {
+            format!("not {}", position.avoid_moves.iter().map(san).collect::<Vec<_>>().join(" "))
+        } else {
+            position.best_moves.iter().map(san).collect::<Vec<_>>().join(" ")
+        };
+        let id = if position.id.is_empty() { format!("#{}", line_num + 1) } else { position.id.clone() };
+
+        println!("{:<16} {:>6} {:>8} {:>16} {:>10} {:>5} {:>10} {:>7.2}s",
+                 id,
+                 if pass { "pass" } else { "FAIL" },
+                 result.best_move().map(san).unwrap_or_else(|| "-".to_string()),
+                 expected,
+                 result.score.to_string(),
+                 result.depth,
+                 result.nodes,
+                 result.time.as_secs_f64());
+    }
+
+    println!();
+    println!("Passed {} of {} ({:.1}%), {} nodes in {:.1}s", passed, total, passed as f64 * 100.0 / total.max(1) as f64, total_nodes, start.elapsed().as_secs_f64());
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod epd_tests {
+    use crate::cli::CliOptions;
+    use crate::epd::{parse_epd, run, solve};
+    use crate::evaluator::PstEvaluator;
+    use crate::search::{SearchLimits, Algorithm};
+    use crate::tt::TranspositionTable;
+
+    #[test]
+    fn epd_test() {
+        let position = parse_epd("2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";").unwrap();
+
+        assert_eq!(position.id, "WAC.001");
+        assert_eq!(position.best_moves.len(), 1);
+        assert_eq!(position.best_moves[0].to_string(), "Qg3-g6");
+        assert!(parse_epd("2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg7;").is_err());
+
+        // a mate in 1, and the only move that doesn't allow one
+        let position = parse_epd("7k/8/6K1/5Q2/8/8/8/8 w - - bm Qf8+; am Qf7; id \"mate\";").unwrap();
+        let limits = SearchLimits { depth: Some(3), ..SearchLimits::default() };
+        let result = solve(&position, Algorithm::AlphaBeta, &limits, 1, &TranspositionTable::new(1), &PstEvaluator::default());
+
+        assert!(position.passes(result.best_move().unwrap()));
+        assert!(!position.passes(&position.avoid_moves[0]));
+        assert_eq!(result.depth, 3);
+
+        // the evaluator is loaded like it is for any other search, and errors are returned rather than panicking
+        let options = |line :&str| CliOptions::parse(&line.split_whitespace().map(|a| a.to_string()).collect::<Vec<_>>()).unwrap();
+
+        assert!(run("/nonexistent/suite.epd", &options("--depth 1")).is_err());
+        assert!(run("/nonexistent/suite.epd", &options("--fen 8/8/8/8/8/8/8/8")).is_err());
+        assert_eq!(options("--evaluator nn").load_evaluator().err(), Some("The nn evaluator needs a weights file".to_string()));
+    }
+}
@@ -0,0 +1,568 @@
+use std::ops::{Add, AddAssign, Sub, Mul};
+
+use shakmaty::{Chess, Setup, Color, Role, Board, Bitboard};
+use shakmaty::attacks::{pawn_attacks, king_attacks};
+
+use crate::weights::Weights;
+
+/// A middlegame and an endgame value, blended by how much material is left
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Tapered(pub i64, pub i64);
+
+/// Material in centipawns, indexed by role: pawn, knight, bishop, rook, queen, king
+const MATERIAL :[Tapered; 6] = [Tapered(100, 120), Tapered(320, 300), Tapered(330, 320), Tapered(500, 520), Tapered(900, 920), Tapered(0, 0)];
+
+/// How much each piece counts towards the middlegame, indexed by role
+const PHASE :[i64; 6] = [0, 1, 1, 2, 4, 0];
+/// The phase of the starting position, anything above counts as a middlegame too
+const MAX_PHASE :i64 = 24;
+
+/// A bonus for the side to move
+const TEMPO :Tapered = Tapered(10, 10);
+
+/// Penalty for every pawn on a file beyond the first
+const DOUBLED_PAWN :Tapered = Tapered(-10, -20);
+/// Penalty for a pawn with no friendly pawns on the files next to it
+const ISOLATED_PAWN :Tapered = Tapered(-15, -10);
+/// Penalty for a pawn behind the pawns next to it, that can't safely advance to catch up
+const BACKWARD_PAWN :Tapered = Tapered(-10, -10);
+/// Bonus for a pawn no enemy pawn can stop, by rank from the pawn's side
+const PASSED_PAWN :[Tapered; 8] = [Tapered(0, 0), Tapered(5, 10), Tapered(10, 15), Tapered(15, 25), Tapered(25, 45), Tapered(40, 75), Tapered(60, 120), Tapered(0, 0)];
+
+/// Bonus per square a piece attacks that isn't ours or guarded by an enemy pawn, indexed by role
+const MOBILITY :[Tapered; 6] = [Tapered(0, 0), Tapered(4, 4), Tapered(5, 5), Tapered(2, 4), Tapered(1, 2), Tapered(0, 0)];
+
+/// Penalty for each enemy piece attacking the squares around our king, indexed by role
+const KING_ATTACKER :[Tapered; 6] = [Tapered(0, 0), Tapered(-15, -5), Tapered(-15, -5), Tapered(-25, -5), Tapered(-40, -10), Tapered(0, 0)];
+/// On the king's file and the files next to it, by rank of our nearest pawn in front of the king, 0 when there's none
+const PAWN_SHELTER :[Tapered; 8] = [Tapered(-25, 0), Tapered(20, 0), Tapered(12, 0), Tapered(5, 0), Tapered(0, 0), Tapered(0, 0), Tapered(0, 0), Tapered(0, 0)];
+/// On the king's file and the files next to it, by rank of the nearest enemy pawn in front of the king, 0 when there's none
+const PAWN_STORM :[Tapered; 8] = [Tapered(0, 0), Tapered(-5, 0), Tapered(-25, 0), Tapered(-15, 0), Tapered(-5, 0), Tapered(0, 0), Tapered(0, 0), Tapered(0, 0)];
+
+const BISHOP_PAIR :Tapered = Tapered(30, 50);
+/// A rook on a file without pawns
+const ROOK_OPEN_FILE :Tapered = Tapered(25, 10);
+/// A rook on a file without our pawns, but with enemy ones
+const ROOK_SEMI_OPEN_FILE :Tapered = Tapered(10, 5);
+const ROOK_ON_SEVENTH :Tapered = Tapered(20, 30);
+/// A knight on ranks 4 to 6, guarded by a pawn, that no enemy pawn can chase away
+const KNIGHT_OUTPOST :Tapered = Tapered(20, 15);
+
+const FILE_A :u64 = 0x0101_0101_0101_0101;
+
+// the tables are laid out like a board with rank 8 at the top, so for white a1 is the first square of the last row
+// each piece has one for the middlegame and one for the endgame
+
+const KING_MG_VALUES :[i64; 64] =
+    [ -30,-40,-40,-50,-50,-40,-40,-30,
+      -30,-40,-40,-50,-50,-40,-40,-30,
+      -30,-40,-40,-50,-50,-40,-40,-30,
+      -30,-40,-40,-50,-50,-40,-40,-30,
+      -20,-30,-30,-40,-40,-30,-30,-20,
+      -10,-20,-20,-20,-20,-20,-20,-10,
+       20, 20,  1,  1,  1,  1, 20, 20,
+       20, 30, 10,  1,  1, 10, 30, 20 ];
+
+const QUEEN_MG_VALUES :[i64; 64] =
+    [ -20,-10,-10, -5, -5,-10,-10,-20,
+      -10,  1,  1,  1,  1,  1,  1,-10,
+      -10,  1,  5,  5,  5,  5,  1,-10,
+       -5,  1,  5,  5,  5,  5,  1, -5,
+        1,  1,  5,  5,  5,  5,  1, -5,
+      -10,  5,  5,  5,  5,  5,  1,-10,
+      -10,  1,  5,  1,  1,  1,  1,-10,
+      -20,-10,-10, -5, -5,-10,-10,-20 ];
+
+const ROOK_MG_VALUES :[i64; 64] =
+    [  1,  1,  1,  1,  1,  1,  1,  1,
+       5, 10, 10, 10, 10, 10, 10,  5,
+      -5,  1,  1,  1,  1,  1,  1, -5,
+      -5,  1,  1,  1,  1,  1,  1, -5,
+      -5,  1,  1,  1,  1,  1,  1, -5,
+      -5,  1,  1,  1,  1,  1,  1, -5,
+      -5,  1,  1,  1,  1,  1,  1, -5,
+       1,  1,  1,  5,  5,  1,  1,  1 ];
+
+const BISHOP_MG_VALUES :[i64; 64] =
+    [ -20,-10,-10,-10,-10,-10,-10,-20,
+      -10,  1,  1,  1,  1,  1,  1,-10,
+      -10,  1,  5, 10, 10,  5,  1,-10,
+      -10,  5,  5, 10, 10,  5,  5,-10,
+      -10,  1, 10, 10, 10, 10,  1,-10,
+      -10, 10, 10, 10, 10, 10, 10,-10,
+      -10,  5,  1,  1,  1,  1,  5,-10,
+      -20,-10,-10,-10,-10,-10,-10,-20 ];
+
+const KNIGHT_MG_VALUES :[i64; 64] =
+    [ -50,-40,-30,-30,-30,-30,-40,-50,
+      -40,-20,  1,  1,  1,  1,-20,-40,
+      -30,  1, 10, 15, 15, 10,  1,-30,
+      -30,  5, 15, 20, 20, 15,  5,-30,
+      -30,  1, 15, 20, 20, 15,  1,-30,
+      -30,  5, 10, 15, 15, 10,  5,-30,
+      -40,-20,  1,  5,  5,  1,-20,-40,
+      -50,-40,-30,-30,-30,-30,-40,-50 ];
+
+const PAWN_MG_VALUES :[i64; 64] =
+    [  1,  1,  1,  1,  1,  1,  1,  1,
+      50, 50, 50, 50, 50, 50, 50, 50,
+      10, 10, 20, 30, 30, 20, 10, 10,
+       5,  5, 10, 25, 25, 10,  5,  5,
+       1,  1,  1, 20, 20,  1,  1,  1,
+       5, -5,-10,  1,  1,-10, -5,  5,
+       5, 10, 10,-20,-20, 10, 10,  5,
+       1,  1,  1,  1,  1,  1,  1,  1 ];
+
+const KING_EG_VALUES :[i64; 64] =
+    [ -50,-40,-30,-20,-20,-30,-40,-50,
+      -30,-20,-10,  0,  0,-10,-20,-30,
+      -30,-10, 20, 30, 30, 20,-10,-30,
+      -30,-10, 30, 40, 40, 30,-10,-30,
+      -30,-10, 30, 40, 40, 30,-10,-30,
+      -30,-10, 20, 30, 30, 20,-10,-30,
+      -30,-30,  0,  0,  0,  0,-30,-30,
+      -50,-30,-30,-30,-30,-30,-30,-50 ];
+
+const QUEEN_EG_VALUES :[i64; 64] =
+    [ -20,-10,-10, -5, -5,-10,-10,-20,
+      -10,  0,  5,  5,  5,  5,  0,-10,
+      -10,  5, 10, 10, 10, 10,  5,-10,
+       -5,  5, 10, 15, 15, 10,  5, -5,
+       -5,  5, 10, 15, 15, 10,  5, -5,
+      -10,  5, 10, 10, 10, 10,  5,-10,
+      -10,  0,  5,  5,  5,  5,  0,-10,
+      -20,-10,-10, -5, -5,-10,-10,-20 ];
+
+const ROOK_EG_VALUES :[i64; 64] =
+    [   5,  5,  5,  5,  5,  5,  5,  5,
+       10, 10, 10, 10, 10, 10, 10, 10,
+        0,  0,  0,  0,  0,  0,  0,  0,
+        0,  0,  0,  0,  0,  0,  0,  0,
+        0,  0,  0,  0,  0,  0,  0,  0,
+        0,  0,  0,  0,  0,  0,  0,  0,
+        0,  0,  0,  0,  0,  0,  0,  0,
+        0,  0,  0,  0,  0,  0,  0,  0 ];
+
+const BISHOP_EG_VALUES :[i64; 64] =
+    [ -15,-10,-10,-10,-10,-10,-10,-15,
+      -10,  0,  0,  0,  0,  0,  0,-10,
+      -10,  0,  5,  5,  5,  5,  0,-10,
+      -10,  0,  5, 10, 10,  5,  0,-10,
+      -10,  0,  5, 10, 10,  5,  0,-10,
+      -10,  0,  5,  5,  5,  5,  0,-10,
+      -10,  0,  0,  0,  0,  0,  0,-10,
+      -15,-10,-10,-10,-10,-10,-10,-15 ];
+
+const KNIGHT_EG_VALUES :[i64; 64] =
+    [ -50,-40,-30,-30,-30,-30,-40,-50,
+      -40,-20,  0,  0,  0,  0,-20,-40,
+      -30,  0, 10, 15, 15, 10,  0,-30,
+      -30,  5, 15, 20, 20, 15,  5,-30,
+      -30,  0, 15, 20, 20, 15,  0,-30,
+      -30,  5, 10, 15, 15, 10,  5,-30,
+      -40,-20,  0,  5,  5,  0,-20,-40,
+      -50,-40,-30,-30,-30,-30,-40,-50 ];
+
+const PAWN_EG_VALUES :[i64; 64] =
+    [   0,  0,  0,  0,  0,  0,  0,  0,
+       60, 60, 60, 60, 60, 60, 60, 60,
+       35, 35, 35, 35, 35, 35, 35, 35,
+       20, 20, 20, 20, 20, 20, 20, 20,
+       10, 10, 10, 10, 10, 10, 10, 10,
+        5,  5,  5,  5,  5,  5,  5,  5,
+        0,  0,  0,  0,  0,  0,  0,  0,
+        0,  0,  0,  0,  0,  0,  0,  0 ];
+
+/// Rough material value of each piece, used to judge captures
+#[inline]
+pub fn piece_value(role :Role) -> i64 {
+    match role {
+        Role::Pawn => 100,
+        Role::Knight => 320,
+        Role::Bishop => 330,
+        Role::Rook => 500,
+        Role::Queen => 900,
+        Role::King => 20_000
+    }
+}
+
+/// The weights the engine was built with, everything above
+pub const BUILTIN_WEIGHTS :Weights = Weights {
+    material: MATERIAL,
+    pst: [
+        pst(&PAWN_MG_VALUES, &PAWN_EG_VALUES),
+        pst(&KNIGHT_MG_VALUES, &KNIGHT_EG_VALUES),
+        pst(&BISHOP_MG_VALUES, &BISHOP_EG_VALUES),
+        pst(&ROOK_MG_VALUES, &ROOK_EG_VALUES),
+        pst(&QUEEN_MG_VALUES, &QUEEN_EG_VALUES),
+        pst(&KING_MG_VALUES, &KING_EG_VALUES)
+    ],
+    tempo: TEMPO,
+    doubled_pawn: DOUBLED_PAWN,
+    isolated_pawn: ISOLATED_PAWN,
+    backward_pawn: BACKWARD_PAWN,
+    passed_pawn: PASSED_PAWN,
+    mobility: MOBILITY,
+    king_attacker: KING_ATTACKER,
+    pawn_shelter: PAWN_SHELTER,
+    pawn_storm: PAWN_STORM,
+    bishop_pair: BISHOP_PAIR,
+    rook_open_file: ROOK_OPEN_FILE,
+    rook_semi_open_file: ROOK_SEMI_OPEN_FILE,
+    rook_on_seventh: ROOK_ON_SEVENTH,
+    knight_outpost: KNIGHT_OUTPOST,
+};
+
+/// Pairs up a middlegame and an endgame table
+const fn pst(mg :&[i64; 64], eg :&[i64; 64]) -> [Tapered; 64] {
+    let mut table = [Tapered(0, 0); 64];
+    let mut square = 0;
+
+    while square < 64 {
+        table[square] = Tapered(mg[square], eg[square]);
+        square += 1;
+    }
+
+    table
+}
+
+/// Given a game, evaluate the board
+/// The evaluation is relative to the side to move: (our_score + tempo) - their_score,
+/// blended between the middlegame and endgame values by the phase
+pub fn evaluate_with(weights :&Weights, game :&Chess) -> i64 {
+    let board = game.board();
+
+    let white_score = side_score(weights, board, Color::White);
+    let black_score = side_score(weights, board, Color::Black);
+
+    // negamax needs the score from the point of view of the side to move
+    let score = if game.turn() == Color::White {
+        (white_score + weights.tempo) - black_score
+    } else {
+        (black_score + weights.tempo) - white_score
+    };
+
+    score.blend(phase(board))
+}
+
+/// How far from the endgame we are, from 0 with only pawns left to MAX_PHASE with all the pieces on the board
+pub fn phase(board :&Board) -> i64 {
+    let phase = (board.occupied() & !board.pawns() & !board.kings()).into_iter()
+        .map(|square| PHASE[board.role_at(square).unwrap() as usize - 1])
+        .sum::<i64>();
+
+    phase.min(MAX_PHASE)
+}
+
+/// Material, piece-square tables, pawn structure, mobility, king safety and piece activity for one side
+fn side_score(weights :&Weights, board :&Board, color :Color) -> Tapered {
+    let mut score = Tapered::default();
+
+    for square in board.by_color(color) {
+        let piece = board.piece_at(square).unwrap();
+
+        // the tables are from white's point of view
+        let square = if color == Color::White { square.flip_vertical() } else { square };
+
+        score += weights.material[piece.role as usize - 1] + weights.pst[piece.role as usize - 1][square as usize];
+    }
+
+    score + pawn_structure(weights, board, color) + mobility(weights, board, color) + king_safety(weights, board, color) + piece_activity(weights, board, color)
+}
+
+/// Doubled, isolated, backward and passed pawns
+fn pawn_structure(weights :&Weights, board :&Board, color :Color) -> Tapered {
+    let ours = board.pawns() & board.by_color(color);
+    let theirs = board.pawns() & board.by_color(!color);
+    let mut score = Tapered::default();
+
+    for file in 0..8 {
+        let count = (ours & file_mask(file)).count() as i64;
+
+        if count > 1 {
+            score += weights.doubled_pawn * (count - 1);
+        }
+    }
+
+    for square in ours {
+        let file = file_mask(square.file() as usize);
+        let adjacent = adjacent_files(square.file() as usize);
+        let ahead = ranks_ahead(color, square.rank() as usize);
+
+        if (ours & adjacent).is_empty() {
+            score += weights.isolated_pawn;
+        } else if (ours & adjacent & !ahead).is_empty() {
+            // all the pawns next to it have moved on, and an enemy pawn guards the square in front
+            let stop = square.offset(if color == Color::White { 8 } else { -8 });
+
+            if stop.is_some_and(|stop| (pawn_attacks(color, stop) & theirs).any()) {
+                score += weights.backward_pawn;
+            }
+        }
+
+        // nothing in front of it, and no enemy pawns that can take it on the way
+        if (theirs & (file | adjacent) & ahead).is_empty() && (ours & file & ahead).is_empty() {
+            score += weights.passed_pawn[relative_rank(color, square.rank() as usize)];
+        }
+    }
+
+    score
+}
+
+/// Safe squares each piece can move to
+fn mobility(weights :&Weights, board :&Board, color :Color) -> Tapered {
+    let ours = board.by_color(color);
+    let guarded = pawn_attacked(board, !color);
+    let mut score = Tapered::default();
+
+    for square in ours & !board.pawns() & !board.kings() {
+        let role = board.role_at(square).unwrap();
+        let moves = board.attacks_from(square) & !ours & !guarded;
+
+        score += weights.mobility[role as usize - 1] * moves.count() as i64;
+    }
+
+    score
+}
+
+/// Enemy pieces bearing down on the king, and the pawns in front of it
+fn king_safety(weights :&Weights, board :&Board, color :Color) -> Tapered {
+    let king = match board.king_of(color) {
+        Some(king) => king,
+        None => return Tapered::default()
+    };
+
+    let mut score = Tapered::default();
+
+    // attackers of the king and the squares around it
+    let zone = king_attacks(king).with(king);
+
+    for square in board.by_color(!color) & !board.pawns() & !board.kings() {
+        if (board.attacks_from(square) & zone).any() {
+            score += weights.king_attacker[board.role_at(square).unwrap() as usize - 1];
+        }
+    }
+
+    // our shelter and their storm on the king's file and the files next to it
+    let ours = board.pawns() & board.by_color(color);
+    let theirs = board.pawns() & board.by_color(!color);
+    let ahead = ranks_ahead(color, king.rank() as usize);
+
+    let king_file = king.file() as usize;
+
+    for file in king_file.saturating_sub(1)..=(king_file + 1).min(7) {
+        let file = file_mask(file);
+
+        score += weights.pawn_shelter[nearest_rank(color, ours & file & ahead)];
+        score += weights.pawn_storm[nearest_rank(color, theirs & file & ahead)];
+    }
+
+    score
+}
+
+/// Bishop pair, rooks on open files and the 7th rank, and knight outposts
+fn piece_activity(weights :&Weights, board :&Board, color :Color) -> Tapered {
+    let ours = board.pawns() & board.by_color(color);
+    let theirs = board.pawns() & board.by_color(!color);
+    let mut score = Tapered::default();
+
+    if (board.bishops() & board.by_color(color)).more_than_one() {
+        score += weights.bishop_pair;
+    }
+
+    for square in board.rooks() & board.by_color(color) {
+        let file = file_mask(square.file() as usize);
+
+        if (board.pawns() & file).is_empty() {
+            score += weights.rook_open_file;
+        } else if (ours & file).is_empty() {
+            score += weights.rook_semi_open_file;
+        }
+
+        if relative_rank(color, square.rank() as usize) == 6 {
+            score += weights.rook_on_seventh;
+        }
+    }
+
+    for square in board.knights() & board.by_color(color) {
+        let rank = relative_rank(color, square.rank() as usize);
+        let chasers = theirs & adjacent_files(square.file() as usize) & ranks_ahead(color, square.rank() as usize);
+
+        if (3..=5).contains(&rank) && (pawn_attacks(!color, square) & ours).any() && chasers.is_empty() {
+            score += weights.knight_outpost;
+        }
+    }
+
+    score
+}
+
+/// Squares attacked by color's pawns
+fn pawn_attacked(board :&Board, color :Color) -> Bitboard {
+    let mut attacked = Bitboard(0);
+
+    for square in board.pawns() & board.by_color(color) {
+        attacked |= pawn_attacks(color, square);
+    }
+
+    attacked
+}
+
+/// The rank of the pawn closest to color's side of the board, counted from that side, 0 when there are none
+#[inline]
+fn nearest_rank(color :Color, pawns :Bitboard) -> usize {
+    let nearest = if color == Color::White { pawns.first() } else { pawns.last() };
+
+    nearest.map_or(0, |square| relative_rank(color, square.rank() as usize))
+}
+
+#[inline]
+fn relative_rank(color :Color, rank :usize) -> usize {
+    if color == Color::White { rank } else { 7 - rank }
+}
+
+impl Tapered {
+    /// The middlegame value when phase is MAX_PHASE, the endgame value when it's 0, and in between otherwise
+    #[inline]
+    pub fn blend(self, phase :i64) -> i64 {
+        (self.0 * phase + self.1 * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+}
+
+impl Add for Tapered {
+    type Output = Tapered;
+
+    fn add(self, other :Tapered) -> Tapered {
+        Tapered(self.0 + other.0, self.1 + other.1)
+    }
+}
+
+impl AddAssign for Tapered {
+    fn add_assign(&mut self, other :Tapered) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Tapered {
+    type Output = Tapered;
+
+    fn sub(self, other :Tapered) -> Tapered {
+        Tapered(self.0 - other.0, self.1 - other.1)
+    }
+}
+
+impl Mul<i64> for Tapered {
+    type Output = Tapered;
+
+    fn mul(self, times :i64) -> Tapered {
+        Tapered(self.0 * times, self.1 * times)
+    }
+}
+
+#[inline]
+fn file_mask(file :usize) -> Bitboard {
+    Bitboard(FILE_A << file)
+}
+
+#[inline]
+fn adjacent_files(file :usize) -> Bitboard {
+    let left = if file > 0 { file_mask(file - 1) } else { Bitboard(0) };
+    let right = if file < 7 { file_mask(file + 1) } else { Bitboard(0) };
+
+    left | right
+}
+
+/// The ranks in front of this one, from color's point of view
+#[inline]
+fn ranks_ahead(color :Color, rank :usize) -> Bitboard {
+    match color {
+        Color::White => Bitboard(u64::MAX.checked_shl(8 * (rank as u32 + 1)).unwrap_or(0)),
+        Color::Black => Bitboard((1_u64 << (8 * rank)) - 1)
+    }
+}
+
+
+#[cfg(test)]
+mod eval_tests {
+    use shakmaty::{Chess, Setup, Color, Role, CastlingMode};
+    use shakmaty::fen::Fen;
+
+    use crate::eval::*;
+
+    fn evaluate(game :&Chess) -> i64 {
+        evaluate_with(&BUILTIN_WEIGHTS, game)
+    }
+
+    fn game(fen :&str) -> Chess {
+        fen.parse::<Fen>().unwrap().position(CastlingMode::Standard).unwrap()
+    }
+
+    /// The same position with the colors swapped, only for FENs without castling or en passant
+    fn mirror(fen :&str) -> String {
+        let parts = fen.split(' ').collect::<Vec<_>>();
+        let swap_case = |rank :&str| rank.chars().map(|c| if c.is_uppercase() { c.to_ascii_lowercase() } else { c.to_ascii_uppercase() }).collect::<String>();
+        let board = parts[0].split('/').rev().map(swap_case).collect::<Vec<_>>().join("/");
+        let turn = if parts[1] == "w" { "b" } else { "w" };
+
+        format!("{} {} {}", board, turn, parts[2..].join(" "))
+    }
+
+    #[test]
+    fn symmetry_test() {
+        assert_eq!(evaluate(&Chess::default()), TEMPO.0);
+
+        for fen in ["r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w - - 4 4",
+                    "6k1/6p1/5p2/8/8/1q6/r4PPP/4Q1K1 w - - 0 1",
+                    "4k3/8/8/3P4/8/P7/P7/4K3 b - - 0 1"] {
+            assert_eq!(evaluate(&game(fen)), evaluate(&game(&mirror(fen))), "{}", fen);
+        }
+
+        // material counts, white is a rook down for a pawn
+        assert!(evaluate(&game("6k1/6p1/5p2/8/8/1q6/r4PPP/4Q1K1 w - - 0 1")) < -300);
+    }
+
+    #[test]
+    fn phase_test() {
+        assert_eq!(phase(Chess::default().board()), MAX_PHASE);
+        assert_eq!(phase(game("4k3/8/8/8/3K4/8/4P3/8 w - - 0 1").board()), 0);
+        assert_eq!(Tapered(100, 20).blend(MAX_PHASE / 2), 60);
+
+        // in a pawn ending the king belongs in the centre, not tucked away in the corner
+        assert!(evaluate(&game("4k3/8/8/8/3K4/8/4P3/8 w - - 0 1")) > evaluate(&game("4k3/8/8/8/8/8/4P3/K7 w - - 0 1")));
+    }
+
+    #[test]
+    fn pawn_structure_test() {
+        // doubled and isolated on the a file with the front one passed, and an isolated passer on d5
+        let board = game("4k3/8/8/3P4/8/P7/P7/4K3 w - - 0 1").board().clone();
+
+        assert_eq!(pawn_structure(&BUILTIN_WEIGHTS, &board, Color::White), DOUBLED_PAWN + ISOLATED_PAWN * 3 + PASSED_PAWN[2] + PASSED_PAWN[4]);
+
+        // e3 is left behind by d4 and can't advance past f5, while d4 is passed
+        let board = game("4k3/8/8/5p2/3P4/4P3/8/4K3 w - - 0 1").board().clone();
+
+        assert_eq!(pawn_structure(&BUILTIN_WEIGHTS, &board, Color::White), BACKWARD_PAWN + PASSED_PAWN[3]);
+        assert_eq!(pawn_structure(&BUILTIN_WEIGHTS, &board, Color::Black), ISOLATED_PAWN);
+    }
+
+    #[test]
+    fn king_safety_test() {
+        // a pawn missing in front of the king, a pawn storming in, and the queen eyeing f2
+        let board = game("6k1/8/8/8/3q4/6p1/5P1P/6K1 w - - 0 1").board().clone();
+
+        assert_eq!(king_safety(&BUILTIN_WEIGHTS, &board, Color::White), PAWN_SHELTER[1] * 2 + PAWN_SHELTER[0] + PAWN_STORM[2] + KING_ATTACKER[Role::Queen as usize - 1]);
+    }
+
+    #[test]
+    fn piece_activity_test() {
+        // both rooks on open files and one on the 7th, the bishop pair, and a knight on d5 black's pawns can't reach
+        let board = game("6k1/R6p/8/3N4/4P3/8/8/2BR1BK1 w - - 0 1").board().clone();
+
+        assert_eq!(piece_activity(&BUILTIN_WEIGHTS, &board, Color::White), BISHOP_PAIR + ROOK_OPEN_FILE * 2 + ROOK_ON_SEVENTH + KNIGHT_OUTPOST);
+        assert_eq!(piece_activity(&BUILTIN_WEIGHTS, &board, Color::Black), Tapered::default());
+
+        // a knight in the corner has 2 moves, one of them guarded by a pawn
+        let board = game("6k1/8/8/8/8/3p4/8/N5K1 w - - 0 1").board().clone();
+
+        assert_eq!(mobility(&BUILTIN_WEIGHTS, &board, Color::White), MOBILITY[Role::Knight as usize - 1]);
+    }
+}
@@ -0,0 +1,252 @@
+use std::fs;
+use std::sync::Arc;
+
+use shakmaty::{Chess, Setup, Color, Role};
+
+use crate::eval::{evaluate_with, BUILTIN_WEIGHTS};
+use crate::weights::Weights;
+
+/// The evaluators that can be picked from the command line or the Evaluator UCI option
+pub const EVALUATORS :[&str; 3] = ["pst", "linear", "nn"];
+
+/// The linear model is fit on inputs and outputs moved this far away from 0, see utils/linear
+const LINEAR_SCALE :f64 = 100.0;
+
+/// Statically scores a position, the search calls this at the leaves
+pub trait Evaluator: Send + Sync {
+    /// The score in centipawns from the point of view of the side to move
+    fn evaluate(&self, game :&Chess) -> i64;
+}
+
+/// The hand written evaluation: material, piece-square tables, pawn structure and piece activity
+#[derive(Debug, Clone)]
+pub struct PstEvaluator {
+    weights: Weights,
+}
+
+impl PstEvaluator {
+    pub fn new(weights :Weights) -> Self {
+        PstEvaluator { weights }
+    }
+}
+
+impl Default for PstEvaluator {
+    fn default() -> Self {
+        PstEvaluator::new(BUILTIN_WEIGHTS)
+    }
+}
+
+impl Evaluator for PstEvaluator {
+    #[inline]
+    fn evaluate(&self, game :&Chess) -> i64 {
+        evaluate_with(&self.weights, game)
+    }
+}
+
+/// So one picked at runtime can be handed to the search like any other
+impl<T :Evaluator + ?Sized> Evaluator for Arc<T> {
+    #[inline]
+    fn evaluate(&self, game :&Chess) -> i64 {
+        (**self).evaluate(game)
+    }
+}
+
+/// A weighted sum of the features, with the weights fit by utils/linear
+pub struct LinearEvaluator {
+    weights: [f64; 65],
+}
+
+impl LinearEvaluator {
+    pub fn new(weights :[f64; 65]) -> Self {
+        LinearEvaluator { weights }
+    }
+
+    /// Reads the 65 weights, separated by whitespace, in the same order as the features
+    pub fn parse(text :&str) -> Result<Self, String> {
+        let weights = parse_numbers(text)?;
+
+        if weights.len() != 65 {
+            return Err(format!("Expected 65 weights, found {}", weights.len()));
+        }
+
+        let mut array = [0.0; 65];
+        array.copy_from_slice(&weights);
+
+        Ok(LinearEvaluator::new(array))
+    }
+}
+
+impl Evaluator for LinearEvaluator {
+    fn evaluate(&self, game :&Chess) -> i64 {
+        let score = features(game).iter().zip(self.weights.iter()).map(|(x, w)| scale(*x) * w).sum::<f64>();
+
+        unscale(score).round() as i64
+    }
+}
+
+/// A fully connected network with ReLU activations on the hidden layers and a linear output, so
+/// scores can be negative, the same shape scripts/train.py builds
+pub struct NnEvaluator {
+    layers: Vec<Layer>,
+}
+
+struct Layer {
+    outputs: usize,
+    /// inputs x outputs, in the same order as a Keras kernel
+    weights: Vec<f64>,
+    biases: Vec<f64>,
+}
+
+impl NnEvaluator {
+    /// Reads the layer sizes on the first line, starting with the 65 inputs, then for each layer
+    /// a line with its weights and a line with its biases, like scripts/train.py writes them
+    pub fn parse(text :&str) -> Result<Self, String> {
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+
+        let sizes = parse_numbers(lines.next().ok_or("Missing the layer sizes")?)?.iter().map(|s| *s as usize).collect::<Vec<_>>();
+
+        if sizes.first() != Some(&65) || sizes.last() != Some(&1) {
+            return Err(format!("Expected 65 inputs and 1 output, found {:?}", sizes));
+        }
+
+        let mut layers = Vec::new();
+
+        for window in sizes.windows(2) {
+            let (inputs, outputs) = (window[0], window[1]);
+            let weights = parse_numbers(lines.next().ok_or("Missing layer weights")?)?;
+            let biases = parse_numbers(lines.next().ok_or("Missing layer biases")?)?;
+
+            if weights.len() != inputs * outputs || biases.len() != outputs {
+                return Err(format!("Wrong number of weights or biases for a {}x{} layer", inputs, outputs));
+            }
+
+            layers.push(Layer { outputs, weights, biases });
+        }
+
+        Ok(NnEvaluator { layers })
+    }
+}
+
+impl Evaluator for NnEvaluator {
+    fn evaluate(&self, game :&Chess) -> i64 {
+        let mut values = features(game).to_vec();
+
+        for (i, layer) in self.layers.iter().enumerate() {
+            let mut outputs = layer.biases.clone();
+
+            for (input, row) in values.iter().zip(layer.weights.chunks(layer.outputs)) {
+                for (output, weight) in outputs.iter_mut().zip(row) {
+                    *output += input * weight;
+                }
+            }
+
+            values = if i + 1 < self.layers.len() {
+                outputs.into_iter().map(|v| v.max(0.0)).collect()
+            } else {
+                outputs
+            };
+        }
+
+        values[0].round() as i64
+    }
+}
+
+/// Builds an evaluator by name, pst uses the given weights while linear and nn need a file with theirs
+pub fn load_evaluator(name :&str, path :Option<&str>, weights :&Weights) -> Result<Arc<dyn Evaluator>, String> {
+    let read = |path :Option<&str>| {
+        let path = path.ok_or(format!("The {} evaluator needs a weights file", name))?;
+
+        fs::read_to_string(path).map_err(|e| format!("Error reading {}: {}", path, e))
+    };
+
+    match name.to_lowercase().as_str() {
+        "pst" => Ok(Arc::new(PstEvaluator::new(weights.clone()))),
+        "linear" => Ok(Arc::new(LinearEvaluator::parse(&read(path)?)?)),
+        "nn" => Ok(Arc::new(NnEvaluator::parse(&read(path)?)?)),
+        _ => Err(format!("Unknown evaluator: {}", name))
+    }
+}
+
+/// The inputs of the learned models, the same as scripts/fen2vec.py: who's turn it is, then the
+/// Stockfish value of the piece on each square from a1 to h8, negative for black
+pub fn features(game :&Chess) -> [f64; 65] {
+    let board = game.board();
+    let mut features = [0.0; 65];
+
+    features[0] = if game.turn() == Color::White { 50_000.0 } else { -50_000.0 };
+
+    for square in board.occupied() {
+        let piece = board.piece_at(square).unwrap();
+        let value = match piece.role {
+            Role::Pawn => 126.0,
+            Role::Knight => 781.0,
+            Role::Bishop => 825.0,
+            Role::Rook => 1276.0,
+            Role::Queen => 2538.0,
+            Role::King => 32_000.0
+        };
+
+        features[square as usize + 1] = if piece.color == Color::White { value } else { -value };
+    }
+
+    features
+}
+
+#[inline]
+fn scale(x :f64) -> f64 {
+    if x == 0.0 { x } else { x + LINEAR_SCALE.copysign(x) }
+}
+
+#[inline]
+fn unscale(x :f64) -> f64 {
+    if x == 0.0 { x } else { x - LINEAR_SCALE.copysign(x) }
+}
+
+fn parse_numbers(text :&str) -> Result<Vec<f64>, String> {
+    text.split_whitespace().map(|n| n.parse::<f64>().map_err(|_| format!("Invalid number: {}", n))).collect()
+}
+
+
+#[cfg(test)]
+mod evaluator_tests {
+    use shakmaty::{Chess, CastlingMode};
+    use shakmaty::fen::Fen;
+
+    use crate::evaluator::{Evaluator, LinearEvaluator, NnEvaluator, features};
+
+    #[test]
+    fn learned_test() {
+        let game :Chess = "4k3/8/8/8/8/8/8/R3K3 b - - 0 1".parse::<Fen>().unwrap().position(CastlingMode::Standard).unwrap();
+
+        // the rook on a1, the kings on e1 and e8, and black to move
+        assert_eq!(features(&game)[1], 1276.0);
+        assert_eq!(features(&game)[5] + features(&game)[61], 0.0);
+        assert_eq!(features(&game)[0], -50_000.0);
+
+        // only the rook's square counts, 1 + 1276 scaled, then unscaled
+        let mut weights = [0.0; 65];
+        weights[1] = 1.0;
+
+        assert_eq!(LinearEvaluator::new(weights).evaluate(&game), 1276);
+        assert!(LinearEvaluator::parse("1 2 3").is_err());
+
+        // a single layer network doing the same, with a bias of 24
+        let text = format!("65 1\n{}\n24\n", weights.iter().map(|w| w.to_string()).collect::<Vec<_>>().join(" "));
+
+        assert_eq!(NnEvaluator::parse(&text).unwrap().evaluate(&game), 1300);
+
+        // the output is linear, so black's rook makes it negative
+        let black :Chess = "r3k3/8/8/8/8/8/8/4K3 w - - 0 1".parse::<Fen>().unwrap().position(CastlingMode::Standard).unwrap();
+        let mut weights = [0.0; 65];
+        weights[57] = 1.0;
+        let text = format!("65 1\n{}\n0\n", weights.iter().map(|w| w.to_string()).collect::<Vec<_>>().join(" "));
+
+        assert_eq!(NnEvaluator::parse(&text).unwrap().evaluate(&black), -1276);
+
+        // while a hidden layer's ReLU cuts it off at 0, before the output layer adds a bias of -5
+        let text = format!("65 1 1\n{}\n0\n1\n-5\n", weights.iter().map(|w| w.to_string()).collect::<Vec<_>>().join(" "));
+
+        assert_eq!(NnEvaluator::parse(&text).unwrap().evaluate(&black), -5);
+        assert!(NnEvaluator::parse("65 2 1\n1 2\n").is_err());
+    }
+}
@@ -0,0 +1,321 @@
+use std::convert::TryFrom;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use rayon::ThreadPoolBuilder;
+use rayon::prelude::*;
+
+use shakmaty::{Chess, Color};
+
+use crate::selfplay::{Adjudication, Engine, Game, MatchScore, TimeControl, opening, play_game, read_openings, result_string};
+use crate::uci_client::UciClient;
+
+pub const USAGE :&str = "--engine [name=]command ... [--fishermann key=value,...] [--option name=value] [--openings file] [--games N] \
+[--tc seconds+inc] [--concurrency N] [--pgn file] [--resign cp,moves] [--draw cp,moves,from move] [--max-moves N]";
+
+/// An external engine to play, by the command that starts it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Opponent {
+    /// Taken from the engine's id name when not given
+    pub name: Option<String>,
+    pub command: String,
+}
+
+impl Opponent {
+    /// Parses "name=command args", or just "command args"
+    pub fn parse(spec :&str) -> Result<Opponent, String> {
+        let (name, command) = match spec.split_once('=') {
+            Some((name, command)) if !name.contains(char::is_whitespace) => (Some(name.to_string()), command),
+            _ => (None, spec)
+        };
+
+        if command.trim().is_empty() {
+            return Err(format!("No command for engine: {}", spec));
+        }
+
+        Ok(Opponent { name, command: command.trim().to_string() })
+    }
+
+    /// Starts a fresh process for a game, with the UCI options set
+    pub fn start(&self, options :&[(String, String)]) -> Result<UciClient, String> {
+        let mut client = UciClient::start(&self.command, self.name.as_deref())?;
+
+        for (name, value) in options {
+            client.set_option(name, value)?;
+        }
+
+        client.new_game()?;
+
+        Ok(client)
+    }
+}
+
+/// Plays one game between fishermann and an opponent, engine_white says who has white
+pub fn play(engine :&Engine, opponent :&Opponent, options :&[(String, String)], opening :&Chess, engine_white :bool, time_control :&TimeControl, adjudication :&Adjudication) -> Result<Game, String> {
+    let mut player = engine.player();
+    let mut client = opponent.start(options)?;
+
+    Ok(if engine_white {
+        play_game(&mut player, &mut client, opening, time_control, Some(adjudication))
+    } else {
+        play_game(&mut client, &mut player, opening, time_control, Some(adjudication))
+    })
+}
+
+/// One row for each opponent, with fishermann's score against it, then the total
+pub fn crosstable(name :&str, scores :&[(String, MatchScore)]) -> String {
+    let mut table = String::new();
+    let width = scores.iter().map(|(opponent, _)| opponent.len()).chain([name.len(), 5]).max().unwrap_or(0);
+    let total = scores.iter().fold(MatchScore::default(), |total, (_, score)| MatchScore {
+        wins: total.wins + score.wins,
+        draws: total.draws + score.draws,
+        losses: total.losses + score.losses
+    });
+
+    writeln!(table, "{:<width$} {:>6} {:>5} {:>5} {:>5} {:>7} {:>16}", name, "GAMES", "+", "=", "-", "SCORE", "ELO", width = width).unwrap();
+
+    for (opponent, score) in scores.iter().map(|(opponent, score)| (opponent.as_str(), score)).chain([("Total", &total)]) {
+        let points = score.wins as f64 + score.draws as f64 / 2.0;
+        let (elo, margin) = score.elo();
+
+        writeln!(table, "{:<width$} {:>6} {:>5} {:>5} {:>5} {:>6.1}% {:>16}",
+                 opponent,
+                 score.games(),
+                 score.wins,
+                 score.draws,
+                 score.losses,
+                 points * 100.0 / score.games().max(1) as f64,
+                 format!("{:.1} +/- {:.1}", elo, margin),
+                 width = width).unwrap();
+    }
+
+    table
+}
+
+/// The options of a gauntlet from the command line, fishermann's settings are for Engine::parse
+#[derive(Debug, Clone, PartialEq)]
+pub struct GauntletOptions {
+    pub engine: String,
+    pub opponents: Vec<Opponent>,
+    /// UCI options set on every opponent
+    pub options: Vec<(String, String)>,
+    /// Without a file of openings every game starts from the start position
+    pub openings: Option<String>,
+    pub games: usize,
+    pub time_control: TimeControl,
+    pub adjudication: Adjudication,
+    pub concurrency: usize,
+    pub pgn: Option<String>,
+}
+
+impl Default for GauntletOptions {
+    fn default() -> Self {
+        GauntletOptions {
+            engine: String::new(),
+            opponents: Vec::new(),
+            options: Vec::new(),
+            openings: None,
+            games: 10,
+            time_control: TimeControl { base: Duration::from_secs(10), increment: Duration::from_millis(100) },
+            adjudication: Adjudication::default(),
+            concurrency: 1,
+            pgn: None
+        }
+    }
+}
+
+impl GauntletOptions {
+    /// Parses the arguments after the subcommand, at least one engine is needed
+    pub fn parse(args :&[String]) -> Result<GauntletOptions, String> {
+        let mut options = GauntletOptions::default();
+        let mut args = args.iter();
+
+        while let Some(flag) = args.next() {
+            let value = args.next().map(|v| v.to_string()).ok_or(format!("Missing value for {}", flag))?;
+            let number = |v :&str| v.parse::<usize>().map_err(|_| format!("Invalid number for {}: {}", flag, v));
+            let numbers = |count :usize| {
+                let numbers = value.split(',').map(|n| n.trim().parse::<u32>().map_err(|_| format!("Invalid number for {}: {}", flag, n))).collect::<Result<Vec<_>, _>>()?;
+
+                if numbers.len() == count { Ok(numbers) } else { Err(format!("Expected {} numbers for {}: {}", count, flag, value)) }
+            };
+            let centipawns = |n :u32| i32::try_from(n).map_err(|_| format!("Score out of range for {}: {}", flag, n));
+
+            match flag.as_str() {
+                "--engine" => options.opponents.push(Opponent::parse(&value)?),
+                "--fishermann" => options.engine = value,
+                "--option" => {
+                    let (name, value) = value.split_once('=').ok_or(format!("Expected name=value for --option: {}", value))?;
+                    options.options.push((name.to_string(), value.to_string()));
+                },
+                "--openings" => options.openings = Some(value),
+                "--games" => options.games = number(&value)?,
+                "--tc" => options.time_control = TimeControl::parse(&value)?,
+                "--concurrency" => options.concurrency = number(&value)?.max(1),
+                "--pgn" => options.pgn = Some(value),
+                "--resign" => {
+                    let numbers = numbers(2)?;
+                    options.adjudication.resign_score = centipawns(numbers[0])?;
+                    options.adjudication.resign_moves = numbers[1] as usize;
+                },
+                "--draw" => {
+                    let numbers = numbers(3)?;
+                    options.adjudication.draw_score = centipawns(numbers[0])?;
+                    options.adjudication.draw_moves = numbers[1] as usize;
+                    options.adjudication.draw_after = numbers[2];
+                },
+                "--max-moves" => options.adjudication.max_moves = number(&value)?,
+                _ => return Err(format!("Unknown option: {}", flag))
+            }
+        }
+
+        if options.opponents.is_empty() {
+            return Err("No engines to play, give at least one --engine".to_string());
+        }
+
+        Ok(options)
+    }
+}
+
+/// Plays games games against each opponent, each opening twice with the colours swapped, running concurrency games at once
+///
+/// Each game gets a fresh engine process. The games are written to the PGN file as they finish, then a crosstable is printed.
+pub fn run(options :&GauntletOptions) -> Result<(), String> {
+    let engine = Engine::parse("fishermann", &options.engine)?;
+    let openings = match &options.openings {
+        Some(path) => read_openings(path)?,
+        None => vec![Chess::default()]
+    };
+    let (opponents, games, time_control, adjudication, concurrency) = (&options.opponents, options.games, &options.time_control, &options.adjudication, options.concurrency);
+    let pool = ThreadPoolBuilder::new().num_threads(concurrency).build().map_err(|e| format!("Error creating thread pool: {}", e))?;
+    let pgn = options.pgn.as_ref().map(|path| File::create(path).map_err(|e| format!("Error creating {}: {}", path, e))).transpose()?;
+    let state = Mutex::new((vec![(None, MatchScore::default()); opponents.len()], pgn, 0));
+
+    println!("{} against {} engines, {} games each at {}, {} at a time", engine.name, opponents.len(), games, time_control, concurrency);
+
+    pool.install(|| {
+        (0..opponents.len() * games).into_par_iter().for_each(|n| {
+            let (o, i) = (n / games, n % games);
+            let opening = opening(&openings, i);
+            let engine_white = i % 2 == 0;
+
+            let game = match play(&engine, &opponents[o], &options.options, &opening, engine_white, time_control, adjudication) {
+                Ok(game) => game,
+                Err(e) => {
+                    eprintln!("Game {} against {} not played: {}", n + 1, opponents[o].command, e);
+                    return;
+                }
+            };
+
+            let mut state = state.lock().unwrap();
+            let (scores, pgn, played) = &mut *state;
+            let (name, score) = &mut scores[o];
+
+            *name = Some(if engine_white { game.black.clone() } else { game.white.clone() });
+            *played += 1;
+
+            match game.outcome.winner() {
+                None => score.draws += 1,
+                Some(winner) if (winner == Color::White) == engine_white => score.wins += 1,
+                Some(_) => score.losses += 1
+            }
+
+            if let Some(pgn) = pgn {
+                pgn.write_all(game.to_pgn(*played, time_control).as_bytes()).expect("Error writing PGN file");
+            }
+
+            println!("Game {} {} vs {}: {} {}", *played, game.white, game.black, result_string(game.outcome), game.reason);
+        });
+    });
+
+    let (scores, _, _) = state.into_inner().unwrap();
+    let scores = scores.into_iter().zip(opponents)
+        .map(|((name, score), opponent)| (name.or_else(|| opponent.name.clone()).unwrap_or_else(|| opponent.command.clone()), score))
+        .collect::<Vec<_>>();
+
+    println!();
+    print!("{}", crosstable(&engine.name, &scores));
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod gauntlet_tests {
+    use shakmaty::{Chess, Color, Outcome};
+
+    use crate::cli::parse_position;
+    use crate::gauntlet::{GauntletOptions, Opponent, crosstable, play, run};
+    use crate::selfplay::{Adjudication, Engine, MatchScore, TimeControl};
+
+    /// The mock engine answers every go with the move it's given
+    fn mock(mv :&str) -> Opponent {
+        Opponent::parse(&format!("mock=sh {}/scripts/mock_uci.sh {}", env!("CARGO_MANIFEST_DIR"), mv)).unwrap()
+    }
+
+    #[test]
+    fn gauntlet_test() {
+        assert_eq!(Opponent::parse("sf=stockfish --bench").unwrap(), Opponent { name: Some("sf".to_string()), command: "stockfish --bench".to_string() });
+        assert_eq!(Opponent::parse("./ethereal").unwrap().name, None);
+        assert!(Opponent::parse("sf=").is_err());
+
+        let engine = Engine::parse("fishermann", "algorithm=ab").unwrap();
+        let tc = TimeControl::parse("1+0").unwrap();
+        let adjudication = Adjudication::default();
+
+        // after 1. f3 e5 the mock plays 2. g4 and fishermann mates with Qh4
+        let start = parse_position("rnbqkbnr/pppp1ppp/8/4p3/8/5P2/PPPPP1PP/RNBQKBNR w KQkq - 0 2").unwrap();
+        let game = play(&engine, &mock("g2g4"), &[], &start, false, &tc, &adjudication).unwrap();
+
+        assert_eq!((game.white.as_str(), game.black.as_str()), ("mock", "fishermann"));
+        assert_eq!((game.outcome, game.reason.as_str(), game.moves.len()), (Outcome::Decisive { winner: Color::Black }, "checkmate", 2));
+        assert!(game.to_pgn(1, &tc).contains("2. g4 Qh4# {checkmate} 0-1"));
+
+        // and the other way around, the mock mates fishermann
+        let start = parse_position("rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2").unwrap();
+        let game = play(&engine, &mock("d8h4"), &[], &start, true, &tc, &adjudication).unwrap();
+
+        assert_eq!((game.outcome, game.reason.as_str()), (Outcome::Decisive { winner: Color::Black }, "checkmate"));
+
+        // an illegal move or no move at all forfeits
+        let game = play(&engine, &mock("a1a1"), &[], &Chess::default(), false, &tc, &adjudication).unwrap();
+
+        assert_eq!((game.outcome, game.termination), (Outcome::Decisive { winner: Color::Black }, "rules infraction"));
+        assert_eq!(game.reason, "mock forfeits: illegal move a1a1");
+
+        let tc = TimeControl::parse("0.2+0").unwrap();
+        let game = play(&engine, &mock("sleep"), &[("Hash".to_string(), "16".to_string())], &Chess::default(), false, &tc, &adjudication).unwrap();
+
+        assert_eq!((game.outcome, game.reason.as_str(), game.termination), (Outcome::Decisive { winner: Color::Black }, "mock loses on time", "time forfeit"));
+        assert!(play(&engine, &Opponent::parse("/nonexistent/engine").unwrap(), &[], &start, true, &tc, &adjudication).is_err());
+
+        let table = crosstable("fishermann", &[("mock".to_string(), MatchScore { wins: 3, draws: 1, losses: 0 }), ("other".to_string(), MatchScore { wins: 0, draws: 1, losses: 1 })]);
+        let lines = table.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("fishermann  GAMES"));
+        assert!(lines[1].starts_with("mock            4     3     1     0   87.5%"));
+        assert!(lines[3].starts_with("Total           6     3     2     1   66.7%"));
+
+        // bad arguments are errors, not panics
+        let args = |line :&str| line.split_whitespace().map(|a| a.to_string()).collect::<Vec<_>>();
+        let options = GauntletOptions::parse(&args("--engine sf=stockfish --option Hash=16 --resign 600,4 --draw 5,10,30")).unwrap();
+
+        assert_eq!((options.opponents.len(), options.options[0].1.as_str()), (1, "16"));
+        assert_eq!((options.adjudication.resign_score, options.adjudication.draw_after), (600, 30));
+        assert_eq!(GauntletOptions::parse(&args("--games 4")).unwrap_err(), "No engines to play, give at least one --engine");
+        assert_eq!(GauntletOptions::parse(&args("--engine sf --resign 600")).unwrap_err(), "Expected 2 numbers for --resign: 600");
+        assert_eq!(GauntletOptions::parse(&args("--engine sf --draw 5,x,30")).unwrap_err(), "Invalid number for --draw: x");
+
+        // negative or out of range counts and scores are errors, instead of wrapping around
+        assert_eq!(GauntletOptions::parse(&args("--engine sf --resign 600,-1")).unwrap_err(), "Invalid number for --resign: -1");
+        assert_eq!(GauntletOptions::parse(&args("--engine sf --draw 5,10,-30")).unwrap_err(), "Invalid number for --draw: -30");
+        assert_eq!(GauntletOptions::parse(&args("--engine sf --resign 3000000000,4")).unwrap_err(), "Score out of range for --resign: 3000000000");
+        assert_eq!(GauntletOptions::parse(&args("--engine sf --draw 5,10,5000000000")).unwrap_err(), "Invalid number for --draw: 5000000000");
+        assert_eq!(GauntletOptions::parse(&args("--engine sf --option Hash")).unwrap_err(), "Expected name=value for --option: Hash");
+        assert_eq!(GauntletOptions::parse(&args("--engine")).unwrap_err(), "Missing value for --engine");
+        assert!(run(&GauntletOptions::parse(&args("--engine sf --openings /nonexistent/openings.epd")).unwrap()).is_err());
+    }
+}
@@ -1,261 +1,220 @@
-use std::cmp::max;
-use std::time::Instant;
+use std::env;
+use std::fs;
+use std::process;
+use std::str::FromStr;
+use std::thread;
+
+mod bench;
+mod book;
+mod cli;
+mod epd;
+mod eval;
+mod evaluator;
+mod gauntlet;
+mod movepick;
+mod parallel;
+mod perft;
+mod result;
+mod score;
+mod search;
+mod selfplay;
+mod tablebase;
+mod time;
+mod tt;
+mod tune;
+mod uci;
+mod uci_client;
+mod weights;
+mod zobrist;
+
+use crate::cli::CliOptions;
+use crate::eval::BUILTIN_WEIGHTS;
+use crate::gauntlet::GauntletOptions;
+use crate::selfplay::MatchOptions;
+use crate::uci::UciEngine;
+use crate::weights::Weights;
 
-use shakmaty::{Chess, Position, Setup, Color, Piece, Role, Move, CastlingMode};
-use shakmaty::fen::{board_fen, epd, Fen};
 
-use smallvec::{smallvec, SmallVec};
-
-use rayon::prelude::*;
-
-
-const MAX_DEPTH :usize = 96;
-
-const KING_VALUES :[i64; 64] =
-    [ -30,-40,-40,-50,-50,-40,-40,-30,
-      -30,-40,-40,-50,-50,-40,-40,-30,
-      -30,-40,-40,-50,-50,-40,-40,-30,
-      -30,-40,-40,-50,-50,-40,-40,-30,
-      -20,-30,-30,-40,-40,-30,-30,-20,
-      -10,-20,-20,-20,-20,-20,-20,-10,
-       20, 20,  1,  1,  1,  1, 20, 20,
-       20, 30, 10,  1,  1, 10, 30, 20 ];
-
-const QUEEN_VALUES :[i64; 64] =
-    [ -20,-10,-10, -5, -5,-10,-10,-20,
-      -10,  1,  1,  1,  1,  1,  1,-10,
-      -10,  1,  5,  5,  5,  5,  1,-10,
-       -5,  1,  5,  5,  5,  5,  1, -5,
-        1,  1,  5,  5,  5,  5,  1, -5,
-      -10,  5,  5,  5,  5,  5,  1,-10,
-      -10,  1,  5,  1,  1,  1,  1,-10,
-      -20,-10,-10, -5, -5,-10,-10,-20 ];
-
-const ROOK_VALUES :[i64; 64] =
-    [  1,  1,  1,  1,  1,  1,  1,  1,
-       5, 10, 10, 10, 10, 10, 10,  5,
-      -5,  1,  1,  1,  1,  1,  1, -5,
-      -5,  1,  1,  1,  1,  1,  1, -5,
-      -5,  1,  1,  1,  1,  1,  1, -5,
-      -5,  1,  1,  1,  1,  1,  1, -5,
-      -5,  1,  1,  1,  1,  1,  1, -5,
-       1,  1,  1,  5,  5,  1,  1,  1 ];
-
-const BISHOP_VALUES :[i64; 64] =
-    [ -20,-10,-10,-10,-10,-10,-10,-20,
-      -10,  1,  1,  1,  1,  1,  1,-10,
-      -10,  1,  5, 10, 10,  5,  1,-10,
-      -10,  5,  5, 10, 10,  5,  5,-10,
-      -10,  1, 10, 10, 10, 10,  1,-10,
-      -10, 10, 10, 10, 10, 10, 10,-10,
-      -10,  5,  1,  1,  1,  1,  5,-10,
-      -20,-10,-10,-10,-10,-10,-10,-20 ];
-
-const KNIGHT_VALUES :[i64; 64] =
-    [ -50,-40,-30,-30,-30,-30,-40,-50,
-      -40,-20,  1,  1,  1,  1,-20,-40,
-      -30,  1, 10, 15, 15, 10,  1,-30,
-      -30,  5, 15, 20, 20, 15,  5,-30,
-      -30,  1, 15, 20, 20, 15,  1,-30,
-      -30,  5, 10, 15, 15, 10,  5,-30,
-      -40,-20,  1,  5,  5,  1,-20,-40,
-      -50,-40,-30,-30,-30,-30,-40,-50 ];
-
-const PAWN_VALUES :[i64; 64] =
-    [  1,  1,  1,  1,  1,  1,  1,  1,
-      50, 50, 50, 50, 50, 50, 50, 50,
-      10, 10, 20, 30, 30, 20, 10, 10,
-       5,  5, 10, 25, 25, 10,  5,  5,
-       1,  1,  1, 20, 20,  1,  1,  1,
-       5, -5,-10,  1,  1,-10, -5,  5,
-       5, 10, 10,-20,-20, 10, 10,  5,
-       1,  1,  1,  1,  1,  1,  1,  1 ];
-
-#[inline]
-fn get_value(square :usize, piece :&Piece) -> i64 {
-    match piece.role {
-        Role::Pawn => PAWN_VALUES[square],
-        Role::Knight => KNIGHT_VALUES[square],
-        Role::Bishop => BISHOP_VALUES[square],
-        Role::Rook => ROOK_VALUES[square],
-        Role::Queen => QUEEN_VALUES[square],
-        Role::King => KING_VALUES[square]
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    match args.get(1).map(|s| s.as_str()) {
+        // compare searching with and without quiescence on labeled positions
+        Some("bench") => {
+            let result = arg(&args, 2, "FEN file").and_then(|path| {
+                bench::quiescence(path, arg_or(&args, 3, "depth", 4)?, arg_or(&args, 4, "number of positions", usize::MAX)?);
+                Ok(())
+            });
+
+            if let Err(e) = result {
+                eprintln!("{}", e);
+                eprintln!("Usage: {} bench <fen file> [depth] [positions]", args[0]);
+                process::exit(1);
+            }
+        },
+        // compare the node counts and times of the search algorithms on the same positions
+        Some("compare") => {
+            let result = arg(&args, 2, "FEN file").and_then(|path| {
+                bench::algorithms(path, arg_or(&args, 3, "depth", 4)?, arg_or(&args, 4, "number of positions", usize::MAX)?);
+                Ok(())
+            });
+
+            if let Err(e) = result {
+                eprintln!("{}", e);
+                eprintln!("Usage: {} compare <fen file> [depth] [positions]", args[0]);
+                process::exit(1);
+            }
+        },
+        // the score, PV and statistics of every algorithm's search of each position
+        Some("stats") => {
+            let result = arg(&args, 2, "FEN file").and_then(|path| {
+                let json = match args.get(5).map(|f| f.as_str()) {
+                    None | Some("text") => false,
+                    Some("json") => true,
+                    Some(other) => return Err(format!("Unknown output format: {}", other))
+                };
+
+                bench::results(path, arg_or(&args, 3, "depth", 4)?, arg_or(&args, 4, "number of positions", usize::MAX)?, json);
+                Ok(())
+            });
+
+            if let Err(e) = result {
+                eprintln!("{}", e);
+                eprintln!("Usage: {} stats <fen file> [depth] [positions] [text|json]", args[0]);
+                process::exit(1);
+            }
+        },
+        // the nodes saved by null move pruning and late move reductions
+        Some("pruning") => {
+            let result = arg(&args, 2, "FEN file").and_then(|path| {
+                bench::pruning(path, arg_or(&args, 3, "depth", 6)?, arg_or(&args, 4, "number of positions", usize::MAX)?);
+                Ok(())
+            });
+
+            if let Err(e) = result {
+                eprintln!("{}", e);
+                eprintln!("Usage: {} pruning <fen file> [depth] [positions]", args[0]);
+                process::exit(1);
+            }
+        },
+        // time to depth with more and more threads
+        Some("smp") => {
+            let result = arg(&args, 2, "FEN file").and_then(|path| {
+                let cores = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+
+                bench::threads(path, arg_or(&args, 3, "depth", 6)?, arg_or(&args, 4, "number of positions", usize::MAX)?, arg_or(&args, 5, "number of threads", cores)?);
+                Ok(())
+            });
+
+            if let Err(e) = result {
+                eprintln!("{}", e);
+                eprintln!("Usage: {} smp <fen file> [depth] [positions] [threads]", args[0]);
+                process::exit(1);
+            }
+        },
+        // count the leaves of the move tree, or check the counts of the standard positions
+        Some("perft") => {
+            let result = arg(&args, 2, "depth").and_then(|depth| {
+                let threads = arg_or(&args, 4, "number of threads", 1)?;
+
+                if depth == "suite" {
+                    perft::suite(arg_or(&args, 3, "depth", 4)?, threads);
+                    Ok(())
+                } else {
+                    let depth = depth.parse().map_err(|_| format!("Invalid depth: {}", depth))?;
+
+                    perft::run(args.get(3).map(|f| f.as_str()), depth, threads)
+                }
+            });
+
+            if let Err(e) = result {
+                eprintln!("{}", e);
+                eprintln!("Usage: {} perft <depth> [fen] [threads]", args[0]);
+                eprintln!("       {} perft suite [max depth] [threads]", args[0]);
+                process::exit(1);
+            }
+        },
+        // search the positions of an EPD test suite and check the moves against bm and am
+        Some("epd") => {
+            let result = match args.get(2) {
+                Some(path) => CliOptions::parse(&args[3..]).and_then(|options| epd::run(path, &options)),
+                None => Err("Missing the EPD file".to_string())
+            };
+
+            if let Err(e) = result {
+                eprintln!("{}", e);
+                eprintln!("Usage: {} epd {}", args[0], epd::USAGE);
+                process::exit(1);
+            }
+        },
+        // play two configurations of the engine against each other
+        Some("match") => {
+            if let Err(e) = MatchOptions::parse(&args[2..]).and_then(|options| selfplay::run(&options)) {
+                eprintln!("{}", e);
+                eprintln!("Usage: {} match {}", args[0], selfplay::USAGE);
+                process::exit(1);
+            }
+        },
+        // play fishermann against external UCI engines
+        Some("gauntlet") => {
+            if let Err(e) = GauntletOptions::parse(&args[2..]).and_then(|options| gauntlet::run(&options)) {
+                eprintln!("{}", e);
+                eprintln!("Usage: {} gauntlet {}", args[0], gauntlet::USAGE);
+                process::exit(1);
+            }
+        },
+        // tune the evaluation weights on labeled positions
+        Some("tune") => {
+            let result = arg(&args, 2, "FEN file").and_then(|path| {
+                let out_path = arg(&args, 3, "weights file to write")?;
+                let initial = match args.get(5) {
+                    Some(initial) => Weights::load(initial, &BUILTIN_WEIGHTS)?,
+                    None => BUILTIN_WEIGHTS
+                };
+
+                tune::tune(path, out_path, arg_or(&args, 4, "number of passes", 10)?, initial);
+                Ok(())
+            });
+
+            if let Err(e) = result {
+                eprintln!("{}", e);
+                eprintln!("Usage: {} tune <fen file> <weights out> [passes] [initial weights]", args[0]);
+                process::exit(1);
+            }
+        },
+        // write out the built-in evaluation weights, as a starting point for a weights file
+        Some("dump-weights") => {
+            let text = BUILTIN_WEIGHTS.to_text();
+
+            match args.get(2) {
+                Some(path) => fs::write(path, text).expect("Error writing weights file"),
+                None => print!("{}", text)
+            }
+        },
+        // search positions given on the command line, or speak UCI with the options applied
+        Some(flag) if flag.starts_with("--") => {
+            let result = CliOptions::parse(&args[1..]).and_then(|options| cli::run(&options));
+
+            if let Err(e) = result {
+                eprintln!("{}", e);
+                eprintln!("Usage: {} {}", args[0], cli::USAGE);
+                process::exit(1);
+            }
+        },
+        // otherwise we speak UCI on STDIN & STDOUT
+        _ => UciEngine::new().run()
     }
 }
 
-/// Given a game, evaluate the board
-/// The evaluation is white_score - black_score
-fn evaluate(game :&Chess) -> i64 {
-    let board = game.board();
-
-    let mut white_score = 0_i64;
-    let mut black_score = 0_i64;
-
-    // go through the pieces on the white squares
-    for square in board.by_color(Color::White) {
-        white_score += get_value(square as usize, &board.piece_at(square).unwrap())
-    }
-
-    // then through the black squares, flipping the square
-    for square in board.by_color(Color::Black) {
-        black_score += get_value(square.flip_vertical() as usize, &board.piece_at(square).unwrap())
-    }
-
-    if game.turn() == Color::White {
-        (white_score + 10) - black_score
-    } else {
-        white_score - (black_score + 10)
-    }
+/// A required argument of a command
+fn arg<'a>(args :&'a [String], i :usize, name :&str) -> Result<&'a str, String> {
+    args.get(i).map(|a| a.as_str()).ok_or(format!("Missing the {}", name))
 }
 
-fn negamax_ab(game :&Chess, depth :usize, alpha :&mut i64, beta :i64) -> (i64, SmallVec<[Move; MAX_DEPTH]>) {
-    if depth == 0 {
-        return (evaluate(&game), smallvec![]);
-    }
-
-    // this is all a bit backwards
-    if game.is_checkmate() {
-        // if it's white's turn, and it's in checkmate, then we want a "bad" value
-        // however, it will immediately be negated, so we must return MAX
-        return (if game.turn() == Color::White {
-            i64::MAX
-        } else {
-            i64::MIN
-        }, smallvec![]);
-    }
-
-    let mut value = i64::MIN;
-    let mut stack = smallvec![];
-
-    // generate all the legal moves
-    for mv in game.legal_moves() {
-        let mut new_game = game.clone();
-        new_game.play_unchecked(&mv);
-
-        let mut new_alpha = beta.saturating_neg();
-        let new_beta = alpha.saturating_neg();
-
-        // make the recursive call
-        let (new_value, new_stack) = negamax_ab(&new_game, depth - 1, &mut new_alpha, new_beta);
-        let new_value = new_value.saturating_neg();
-
-        if new_value > value {
-            stack = new_stack;
-            stack.push(mv.clone());
-            value = new_value;
-        }
-
-        *alpha = max(*alpha, value);
-
-        if *alpha >= beta {
-            break
-        }
+/// An optional argument of a command, the default when it isn't given
+fn arg_or<T :FromStr>(args :&[String], i :usize, name :&str, default :T) -> Result<T, String> {
+    match args.get(i) {
+        Some(a) => a.parse().map_err(|_| format!("Invalid {}: {}", name, a)),
+        None => Ok(default)
     }
-
-    // we might not have actually gotten _any_ legal moves here
-    // so we just return a "very bad" value
-    if stack.is_empty() {
-        // println!("STACK EMPTY!!!");
-        let val = if game.turn() == Color::White {
-            i64::MAX
-        } else {
-            i64::MIN
-        };
-
-        (val, smallvec![])
-    } else {
-        (value, stack)
-    }
-}
-
-fn negamax_basic(game :&Chess, depth :usize) -> (i64, SmallVec<[Move; MAX_DEPTH]>) {
-    if depth == 0 {
-        return (evaluate(&game), smallvec![]);
-    }
-
-    // this is all a bit backwards
-    if game.is_checkmate() {
-        // if it's white's turn, and it's in checkmate, then we want a "bad" value
-        // however, it will immediately be negated, so we must return MAX
-        return (if game.turn() == Color::White {
-            i64::MAX
-        } else {
-            i64::MIN
-        }, smallvec![]);
-    }
-
-    let mut value = i64::MIN;
-    let mut stack = smallvec![];
-
-    // generate all the legal moves
-    for mv in game.legal_moves() {
-        let mut new_game = game.clone();
-        new_game.play_unchecked(&mv);
-
-        // make the recursive call
-        let (new_value, new_stack) = negamax_basic(&new_game, depth - 1);
-        let new_value = new_value.saturating_neg();
-
-        // println!("D{} ({}) {}: {}", depth, mv, new_value, moves2string(&new_stack));
-
-        if new_value == value {
-            let mut print_stack = new_stack.clone();
-            print_stack.push(mv.clone());
-            // println!("FOUND EQUAL: {} {} == {} {}", value, moves2string(&stack), new_value, moves2string(&print_stack));
-        }
-
-        if new_value > value {
-            stack = new_stack;
-            stack.push(mv.clone());
-            value = new_value;
-        }
-    }
-
-    // we might not have actually gotten _any_ legal moves here
-    // so we just return a "very bad" value
-    if stack.is_empty() {
-        println!("STACK EMPTY!!!");
-        let val = if game.turn() == Color::White {
-            i64::MAX
-        } else {
-            i64::MIN
-        };
-
-        (val, smallvec![])
-    } else {
-        (value, stack)
-    }
-}
-
-fn moves2string(moves:&SmallVec<[Move; MAX_DEPTH]>) -> String {
-    let ret = moves.iter().rev().map(|mv| {
-        mv.to_string()
-    }).collect::<Vec<_>>();
-
-    format!("{} ({})", ret.join(", "), moves.len())
-}
-
-fn main() {
-    let depth = 7;
-    let fen = "8/8/k7/p7/2K5/1Q6/8/8 w - - 0 1";
-    println!("DEPTH: {} FEN: {}", depth, fen);
-
-    let setup :Fen = fen.parse().expect("Error parsing FEN");
-
-    // let game :Chess = setup.position(CastlingMode::Standard).expect("Error setting up game");
-    // let start = Instant::now();
-    // let (score, moves) = negamax_basic(&game, depth);
-    // println!("{}s:\t{}: {}", start.elapsed().as_secs_f64(), score, moves2string(&moves));
-
-    // let game :Chess = setup.position(CastlingMode::Standard).expect("Error setting up game");
-    let game = Chess::default();
-    let start = Instant::now();
-    let (score, moves) = negamax_ab(&game, depth, &mut i64::MIN, i64::MAX);
-    println!("{}s:\t{}: {}", start.elapsed().as_secs_f64(), score, moves2string(&moves));
-
-    // let game = Chess::default();
-    // let start = Instant::now();
-    // let res = parallel_negamax(&game, depth);
-    // println!("{}s: {:?}", start.elapsed().as_secs_f64(), res);
-
 }
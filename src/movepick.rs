@@ -0,0 +1,301 @@
+use std::cmp::max;
+
+use shakmaty::{Chess, Position, Setup, Color, Move, Role, Square, MoveList};
+
+use crate::eval::piece_value;
+use crate::search::MAX_DEPTH;
+use crate::tt::PackedMove;
+
+/// History scores are halved when they get this large, so old cutoffs fade away
+const MAX_HISTORY :u32 = 1 << 20;
+
+/// Killer moves and history scores, learned from the cutoffs of quiet moves during a search
+pub struct OrderingTables {
+    killers: Vec<[Option<Move>; 2]>,
+    history: Box<[[[u32; 64]; 64]; 2]>,
+}
+
+impl OrderingTables {
+    pub fn new() -> Self {
+        OrderingTables {
+            killers: vec![[None, None]; MAX_DEPTH + 1],
+            history: Box::new([[[0; 64]; 64]; 2])
+        }
+    }
+
+    pub fn killers(&self, ply :usize) -> &[Option<Move>; 2] {
+        &self.killers[ply.min(MAX_DEPTH)]
+    }
+
+    #[inline]
+    pub fn history(&self, color :Color, mv :&Move) -> u32 {
+        match mv.from() {
+            Some(from) => self.history[color as usize][from as usize][mv.to() as usize],
+            None => 0
+        }
+    }
+
+    /// Records a quiet move that caused a beta cutoff
+    pub fn update(&mut self, color :Color, mv :&Move, ply :usize, depth :usize) {
+        let killers = &mut self.killers[ply.min(MAX_DEPTH)];
+
+        if killers[0].as_ref() != Some(mv) {
+            killers[1] = killers[0].take();
+            killers[0] = Some(mv.clone());
+        }
+
+        if let Some(from) = mv.from() {
+            let score = &mut self.history[color as usize][from as usize][mv.to() as usize];
+
+            // deeper cutoffs are worth more, as they prune bigger trees
+            *score += (depth * depth) as u32;
+
+            if *score > MAX_HISTORY {
+                self.history.iter_mut().flatten().flatten().for_each(|s| *s /= 2);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    HashMove,
+    GenCaptures,
+    Captures,
+    Killers,
+    GenQuiets,
+    Quiets,
+    BadCaptures,
+    Done
+}
+
+/// Hands out the legal moves one at a time, best guesses first:
+/// the hash (or PV) move, captures by MVV-LVA, killers, quiet moves by history, then losing captures
+///
+/// Captures are only split into winning and losing when static exchange evaluation is turned on,
+/// otherwise all captures are tried before the quiet moves.
+pub struct MovePicker {
+    stage: Stage,
+    moves: MoveList,
+    hash_move: Option<PackedMove>,
+    use_see: bool,
+    ply: usize,
+    current: Vec<Move>,
+    bad_captures: Vec<Move>,
+}
+
+impl MovePicker {
+    pub fn new(game :&Chess, hash_move :Option<PackedMove>, ply :usize, use_see :bool) -> Self {
+        MovePicker {
+            stage: Stage::HashMove,
+            moves: game.legal_moves(),
+            hash_move,
+            use_see,
+            ply,
+            current: Vec::new(),
+            bad_captures: Vec::new()
+        }
+    }
+
+    pub fn next(&mut self, game :&Chess, tables :&OrderingTables) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::HashMove => {
+                    self.stage = Stage::GenCaptures;
+
+                    // a hash collision can give us a move that isn't legal here, so only take ones we generated
+                    if let Some(hash_move) = self.hash_move.take() {
+                        if let Some(i) = self.moves.iter().position(|m| hash_move.matches(m)) {
+                            return Some(self.moves.swap_remove(i));
+                        }
+                    }
+                },
+                Stage::GenCaptures => {
+                    let mut captures = Vec::new();
+                    let mut i = 0;
+
+                    while i < self.moves.len() {
+                        if is_noisy(&self.moves[i]) {
+                            captures.push(self.moves.swap_remove(i));
+                        } else {
+                            i += 1;
+                        }
+                    }
+
+                    if self.use_see {
+                        let (good, bad) = captures.into_iter().partition(|mv| mv.is_promotion() || see(game, mv) >= 0);
+
+                        captures = good;
+                        self.bad_captures = bad;
+                        sort_by_score(&mut self.bad_captures, mvv_lva);
+                    }
+
+                    sort_by_score(&mut captures, mvv_lva);
+
+                    self.current = captures;
+                    self.stage = Stage::Captures;
+                },
+                Stage::Captures => {
+                    match self.current.pop() {
+                        Some(mv) => return Some(mv),
+                        None => self.stage = Stage::Killers
+                    }
+                },
+                Stage::Killers => {
+                    self.stage = Stage::GenQuiets;
+
+                    // a killer from a sibling node might not be legal here, so only take ones we generated
+                    for killer in tables.killers(self.ply).iter().flatten() {
+                        if let Some(mv) = self.take(killer) {
+                            // come back for the second killer
+                            self.stage = Stage::Killers;
+                            return Some(mv);
+                        }
+                    }
+                },
+                Stage::GenQuiets => {
+                    let color = game.turn();
+
+                    self.current = self.moves.drain(..).collect();
+                    sort_by_score(&mut self.current, |mv| tables.history(color, mv) as i64);
+                    self.stage = Stage::Quiets;
+                },
+                Stage::Quiets => {
+                    match self.current.pop() {
+                        Some(mv) => return Some(mv),
+                        None => self.stage = Stage::BadCaptures
+                    }
+                },
+                Stage::BadCaptures => {
+                    match self.bad_captures.pop() {
+                        Some(mv) => return Some(mv),
+                        None => self.stage = Stage::Done
+                    }
+                },
+                Stage::Done => return None
+            }
+        }
+    }
+
+    /// Removes the move from the ones we have left to hand out
+    fn take(&mut self, mv :&Move) -> Option<Move> {
+        let i = self.moves.iter().position(|m| m == mv)?;
+
+        Some(self.moves.swap_remove(i))
+    }
+}
+
+/// Captures and promotions
+#[inline]
+pub fn is_noisy(mv :&Move) -> bool {
+    mv.is_capture() || mv.is_promotion()
+}
+
+/// Most Valuable Victim - Least Valuable Attacker: take the biggest piece with the smallest one
+#[inline]
+pub fn mvv_lva(mv :&Move) -> i64 {
+    let victim = mv.capture().map(piece_value).unwrap_or(0);
+    let promotion = mv.promotion().map(piece_value).unwrap_or(0);
+
+    victim * 10 + promotion - piece_value(mv.role()) / 10
+}
+
+/// Sorts so the highest scoring move is last, ready to be popped
+pub fn sort_by_score<F :FnMut(&Move) -> i64>(moves :&mut [Move], score :F) {
+    moves.sort_by_cached_key(score);
+}
+
+/// Static exchange evaluation: the material we win (or lose) on the target square of a capture,
+/// if both sides keep recapturing with their least valuable piece
+pub fn see(game :&Chess, mv :&Move) -> i64 {
+    let board = game.board();
+    let (from, to) = match (mv.from(), mv.capture()) {
+        (Some(from), Some(_)) => (from, mv.to()),
+        _ => return 0
+    };
+
+    let mut occupied = board.occupied();
+    let mut gain = [0_i64; 32];
+    let mut depth = 0;
+
+    gain[0] = mv.capture().map(piece_value).unwrap_or(0);
+    occupied.discard(from);
+
+    // the pawn taken en passant isn't on the target square
+    if let Move::EnPassant { .. } = mv {
+        occupied.discard(Square::from_coords(to.file(), from.rank()));
+    }
+
+    let mut on_square = mv.promotion().unwrap_or_else(|| mv.role());
+    let mut side = !game.turn();
+
+    loop {
+        depth += 1;
+
+        // what we'd have if the piece on the square is taken
+        gain[depth] = piece_value(on_square) - gain[depth - 1];
+
+        // neither side can come out ahead by continuing
+        if max(-gain[depth - 1], gain[depth]) < 0 || depth + 1 >= gain.len() {
+            break
+        }
+
+        // attacks_to looks through the pieces we've removed, so x-rays are handled
+        let attackers = board.attacks_to(to, side, occupied) & occupied;
+
+        let next = [Role::Pawn, Role::Knight, Role::Bishop, Role::Rook, Role::Queen, Role::King]
+            .iter()
+            .find_map(|role| (attackers & board.by_role(*role)).first().map(|sq| (*role, sq)));
+
+        match next {
+            Some((role, sq)) => {
+                occupied.discard(sq);
+                on_square = role;
+                side = !side;
+            },
+            None => break
+        }
+    }
+
+    // either side can stop recapturing when it isn't in their favor
+    loop {
+        depth -= 1;
+
+        if depth == 0 {
+            break
+        }
+
+        gain[depth - 1] = -max(-gain[depth - 1], gain[depth]);
+    }
+
+    gain[0]
+}
+
+
+#[cfg(test)]
+mod movepick_tests {
+    use shakmaty::{Chess, CastlingMode};
+    use shakmaty::fen::Fen;
+    use shakmaty::uci::Uci;
+
+    use crate::movepick::see;
+
+    fn see_uci(fen :&str, uci :&str) -> i64 {
+        let game :Chess = fen.parse::<Fen>().unwrap().position(CastlingMode::Standard).unwrap();
+        let mv = uci.parse::<Uci>().unwrap().to_move(&game).unwrap();
+
+        see(&game, &mv)
+    }
+
+    #[test]
+    fn see_test() {
+        // rook takes an undefended pawn
+        assert_eq!(see_uci("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"), 100);
+
+        // knight takes a pawn defended by a pawn, then the queen x-rays through
+        assert_eq!(see_uci("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "d3e5"), -220);
+
+        // queen takes a pawn defended by a pawn
+        assert_eq!(see_uci("4k3/8/3p4/4p3/8/8/8/4QK2 w - - 0 1", "e1e5"), -800);
+    }
+}
@@ -0,0 +1,151 @@
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use rayon::ThreadPoolBuilder;
+
+use shakmaty::{Chess, Move};
+
+use smallvec::SmallVec;
+
+use crate::evaluator::Evaluator;
+use crate::score::Score;
+use crate::search::{SearchContext, SearchLimits, SearchStats, Algorithm, MAX_DEPTH, iterative_deepening, deepen};
+
+/// Lazy SMP: helper threads search the same position as the main thread, sharing nothing but the
+/// transposition table. They fill it with results the main thread picks up, and because half of them
+/// start one depth ahead they don't all walk the tree in lock step.
+///
+/// The main thread runs iterative_deepening with the given context, limits and callback, and its result
+/// is the one returned. The helpers are stopped as soon as it's done, after which ctx.nodes and ctx.stats
+/// cover all the threads. With a single thread this is just iterative_deepening, so it's deterministic.
+pub fn parallel_negamax<E :Evaluator, F>(ctx :&mut SearchContext<E>, threads :usize, algorithm :Algorithm, game :&Chess, limits :&SearchLimits, on_iteration :F) -> (Score, SmallVec<[Move; MAX_DEPTH]>)
+    where F: FnMut(&SearchContext<E>, usize, Score, &SmallVec<[Move; MAX_DEPTH]>) + Send
+{
+    if threads <= 1 {
+        return iterative_deepening(ctx, algorithm, game, limits, on_iteration);
+    }
+
+    let pool = ThreadPoolBuilder::new().num_threads(threads).build().expect("Error creating thread pool");
+
+    let shared_nodes = Arc::new(AtomicU64::new(0));
+    let helpers_stop = Arc::new(AtomicBool::new(false));
+    let helper_stats = Mutex::new(SearchStats::default());
+
+    // the helpers only stop when the main thread is done, or at the depth limit
+    let helper_limits = SearchLimits { depth: limits.depth, ..SearchLimits::default() };
+    let tt = ctx.tt;
+    let eval = ctx.eval;
+    let options = ctx.options.clone();
+    let tablebase = ctx.tablebase();
+    let history = ctx.history().to_vec();
+
+    ctx.set_shared_nodes(Some(shared_nodes.clone()));
+
+    let result = pool.scope(|s| {
+        for id in 1..threads {
+            let shared_nodes = shared_nodes.clone();
+            let helpers_stop = helpers_stop.clone();
+            let options = options.clone();
+            let history = history.clone();
+            let helper_limits = &helper_limits;
+            let helper_stats = &helper_stats;
+
+            s.spawn(move |_| {
+                let mut helper = SearchContext::new(helpers_stop, tt, eval, options);
+                helper.set_shared_nodes(Some(shared_nodes));
+                helper.set_history(history);
+                helper.set_tablebase(tablebase);
+
+                deepen(&mut helper, algorithm, game, helper_limits, 1 + id % 2, |_, _, _, _| ());
+
+                helper.flush_nodes();
+                *helper_stats.lock().unwrap() += helper.stats;
+            });
+        }
+
+        let result = iterative_deepening(ctx, algorithm, game, limits, on_iteration);
+
+        helpers_stop.store(true, Ordering::Relaxed);
+
+        result
+    });
+
+    ctx.flush_nodes();
+    ctx.nodes = shared_nodes.load(Ordering::Relaxed);
+    ctx.stats += helper_stats.into_inner().unwrap();
+    ctx.set_shared_nodes(None);
+
+    result
+}
+
+
+#[cfg(test)]
+mod parallel_tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use shakmaty::{Chess, Position, CastlingMode};
+    use shakmaty::fen::Fen;
+
+    use crate::evaluator::PstEvaluator;
+    use crate::parallel::parallel_negamax;
+    use crate::search::{SearchContext, SearchLimits, SearchOptions, Algorithm, iterative_deepening};
+    use crate::tt::TranspositionTable;
+
+    const FEN :&str = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4";
+
+    #[test]
+    fn single_thread_test() {
+        let game :Chess = FEN.parse::<Fen>().unwrap().position(CastlingMode::Standard).unwrap();
+        let eval = PstEvaluator::default();
+        let limits = SearchLimits { depth: Some(5), ..SearchLimits::default() };
+
+        // one thread is just iterative deepening, so it finds exactly the same
+        let tt = TranspositionTable::new(1);
+        let mut ctx = SearchContext::new(Arc::new(AtomicBool::new(false)), &tt, &eval, SearchOptions::default());
+        let parallel = parallel_negamax(&mut ctx, 1, Algorithm::Pvs, &game, &limits, |_, _, _, _| ());
+        let parallel_nodes = ctx.nodes;
+
+        let tt = TranspositionTable::new(1);
+        let mut ctx = SearchContext::new(Arc::new(AtomicBool::new(false)), &tt, &eval, SearchOptions::default());
+        let serial = iterative_deepening(&mut ctx, Algorithm::Pvs, &game, &limits, |_, _, _, _| ());
+
+        assert_eq!(parallel, serial);
+        assert_eq!(parallel_nodes, ctx.nodes);
+    }
+
+    #[test]
+    fn threads_test() {
+        let game :Chess = FEN.parse::<Fen>().unwrap().position(CastlingMode::Standard).unwrap();
+        let eval = PstEvaluator::default();
+        let tt = TranspositionTable::new(4);
+
+        // a depth limit ends the search for every thread
+        let mut ctx = SearchContext::new(Arc::new(AtomicBool::new(false)), &tt, &eval, SearchOptions::default());
+        let limits = SearchLimits { depth: Some(4), ..SearchLimits::default() };
+        let (_, moves) = parallel_negamax(&mut ctx, 4, Algorithm::Pvs, &game, &limits, |_, _, _, _| ());
+
+        assert!(game.is_legal(moves.last().unwrap()));
+        assert!(ctx.nodes > 0);
+
+        // without limits only the stop flag ends it, and the last finished iteration still gives a move
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut ctx = SearchContext::new(stop.clone(), &tt, &eval, SearchOptions::default());
+        let start = Instant::now();
+
+        let stopper = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(300));
+            stop.store(true, Ordering::Relaxed);
+        });
+
+        let (_, moves) = parallel_negamax(&mut ctx, 4, Algorithm::Pvs, &game, &SearchLimits::default(), |_, _, _, _| ());
+
+        stopper.join().unwrap();
+
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(ctx.stopped());
+        assert!(game.is_legal(moves.last().unwrap()));
+    }
+}
@@ -0,0 +1,152 @@
+use std::time::Instant;
+
+use rayon::ThreadPoolBuilder;
+use rayon::prelude::*;
+
+use shakmaty::{Chess, Position, CastlingMode};
+use shakmaty::fen::Fen;
+
+/// Standard perft positions and their node counts at depth 1, 2, 3, ... from https://www.chessprogramming.org/Perft_Results
+const SUITE :[(&str, &str, &[u64]); 6] = [
+    ("start", "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", &[20, 400, 8_902, 197_281, 4_865_609, 119_060_324]),
+    ("kiwipete", "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", &[48, 2_039, 97_862, 4_085_603, 193_690_690]),
+    ("position 3", "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", &[14, 191, 2_812, 43_238, 674_624, 11_030_083]),
+    ("position 4", "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", &[6, 264, 9_467, 422_333, 15_833_292]),
+    ("position 5", "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", &[44, 1_486, 62_379, 2_103_487, 89_941_194]),
+    ("position 6", "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10", &[46, 2_079, 89_890, 3_894_594, 164_075_551]),
+];
+
+/// Counts the leaf nodes of the legal move tree, moving the same way the search does
+pub fn perft(game :&Chess, depth :usize) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let moves = game.legal_moves();
+
+    // no need to play the last moves just to count them
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    moves.iter().map(|mv| {
+        let mut new_game = game.clone();
+        new_game.play_unchecked(mv);
+
+        perft(&new_game, depth - 1)
+    }).sum()
+}
+
+/// The perft of each root move, with the root moves split across threads, and the total
+fn divide(game :&Chess, depth :usize, threads :usize) -> (Vec<(String, u64)>, u64) {
+    // no moves are played at depth 0, the root is the only leaf
+    if depth == 0 {
+        return (Vec::new(), 1);
+    }
+
+    let pool = ThreadPoolBuilder::new().num_threads(threads).build().expect("Error creating thread pool");
+    let moves = game.legal_moves();
+
+    let counts :Vec<(String, u64)> = pool.install(|| {
+        moves.par_iter().map(|mv| {
+            let mut new_game = game.clone();
+            new_game.play_unchecked(mv);
+
+            (mv.to_uci(CastlingMode::Standard).to_string(), perft(&new_game, depth - 1))
+        }).collect()
+    });
+    let total = counts.iter().map(|(_, nodes)| nodes).sum();
+
+    (counts, total)
+}
+
+/// Prints the node count of every root move, then the total and the speed
+pub fn run(fen :Option<&str>, depth :usize, threads :usize) -> Result<(), String> {
+    let game = match fen {
+        Some(fen) => parse_fen(fen)?,
+        None => Chess::default()
+    };
+
+    let start = Instant::now();
+    let (mut counts, total) = divide(&game, depth, threads);
+    let elapsed = start.elapsed().as_secs_f64();
+
+    counts.sort();
+
+    for (mv, nodes) in &counts {
+        println!("{}: {}", mv, nodes);
+    }
+
+    println!();
+    println!("Nodes: {}", total);
+    println!("Time: {:.3}s", elapsed);
+    println!("NPS: {:.0}", total as f64 / elapsed.max(0.001));
+
+    Ok(())
+}
+
+/// Runs the standard positions up to max_depth, checking the node counts
+pub fn suite(max_depth :usize, threads :usize) {
+    let mut failed = 0;
+    let mut total_nodes = 0;
+    let start = Instant::now();
+
+    println!("{:>12} {:>5} {:>12} {:>12} {:>12} {:>6}", "POSITION", "DEPTH", "NODES", "EXPECTED", "NPS", "RESULT");
+
+    for (name, fen, expected) in SUITE.iter() {
+        let game = parse_fen(fen).expect("Invalid perft suite FEN");
+
+        for (depth, expected) in expected.iter().enumerate().map(|(i, e)| (i + 1, *e)).take(max_depth) {
+            let depth_start = Instant::now();
+            let (_, nodes) = divide(&game, depth, threads);
+            let nps = nodes as f64 / depth_start.elapsed().as_secs_f64().max(0.001);
+
+            total_nodes += nodes;
+
+            if nodes != expected {
+                failed += 1;
+            }
+
+            println!("{:>12} {:>5} {:>12} {:>12} {:>12.0} {:>6}", name, depth, nodes, expected, nps, if nodes == expected { "ok" } else { "FAIL" });
+        }
+    }
+
+    let elapsed = start.elapsed().as_secs_f64();
+
+    println!();
+    println!("{} failed, {} nodes in {:.3}s, {:.0} NPS", failed, total_nodes, elapsed, total_nodes as f64 / elapsed.max(0.001));
+}
+
+fn parse_fen(fen :&str) -> Result<Chess, String> {
+    let setup :Fen = fen.trim().parse().map_err(|e| format!("Invalid FEN {}: {}", fen, e))?;
+
+    setup.position(CastlingMode::Standard).map_err(|e| format!("Illegal position {}: {}", fen, e))
+}
+
+
+#[cfg(test)]
+mod perft_tests {
+    use crate::perft::{SUITE, parse_fen, perft, divide};
+
+    #[test]
+    fn suite_test() {
+        // the shallow depths of the suite, the rest is for perft suite
+        for (name, fen, expected) in SUITE.iter() {
+            let game = parse_fen(fen).unwrap();
+
+            for depth in 1..=2 {
+                assert_eq!(perft(&game, depth), expected[depth - 1], "{} depth {}", name, depth);
+            }
+        }
+
+        let game = parse_fen(SUITE[1].1).unwrap();
+        let (counts, total) = divide(&game, 3, 2);
+
+        assert_eq!(counts.len(), 48);
+        assert_eq!((counts.iter().map(|(_, nodes)| nodes).sum::<u64>(), total), (97_862, 97_862));
+
+        // depth 0 counts the position itself, without any moves
+        assert_eq!(perft(&game, 0), 1);
+        assert_eq!(divide(&game, 0, 2), (Vec::new(), 1));
+    }
+}
@@ -0,0 +1,177 @@
+use std::time::{Duration, Instant};
+
+use shakmaty::{Chess, Move, CastlingMode};
+
+use smallvec::SmallVec;
+
+use crate::evaluator::Evaluator;
+use crate::parallel::parallel_negamax;
+use crate::score::Score;
+use crate::search::{SearchContext, SearchLimits, Algorithm, MAX_DEPTH};
+
+/// What a search found, and how hard it worked to find it, so algorithms can be compared on the same positions
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub score: Score,
+    /// The principal variation, root move first
+    pub pv: Vec<Move>,
+    /// The last completed iteration
+    pub depth: usize,
+    pub seldepth: usize,
+    pub nodes: u64,
+    pub qnodes: u64,
+    pub time: Duration,
+    pub tt_hits: u64,
+    pub tb_hits: u64,
+    pub beta_cutoffs: u64,
+    pub first_move_cutoffs: u64,
+    /// How many times more nodes the last iteration took than the one before it
+    pub branching_factor: f64,
+}
+
+impl SearchResult {
+    /// Searches with parallel_negamax, and collects the result and the statistics of the search
+    pub fn search<E :Evaluator>(ctx :&mut SearchContext<E>, threads :usize, algorithm :Algorithm, game :&Chess, limits :&SearchLimits) -> SearchResult {
+        SearchResult::search_with(ctx, threads, algorithm, game, limits, |_, _, _, _| ())
+    }
+
+    /// Like search, also calling on_iteration after each iteration like parallel_negamax does
+    pub fn search_with<E :Evaluator, F>(ctx :&mut SearchContext<E>, threads :usize, algorithm :Algorithm, game :&Chess, limits :&SearchLimits, mut on_iteration :F) -> SearchResult
+        where F: FnMut(&SearchContext<E>, usize, Score, &SmallVec<[Move; MAX_DEPTH]>) + Send
+    {
+        let start = Instant::now();
+        let mut depth = 0;
+        let mut iteration_nodes = vec![0];
+
+        let (score, moves) = parallel_negamax(ctx, threads, algorithm, game, limits, |ctx, d, score, moves| {
+            depth = d;
+            iteration_nodes.push(ctx.total_nodes());
+            on_iteration(ctx, d, score, moves);
+        });
+
+        // the nodes are cumulative, so each iteration took the difference with the one before
+        let branching_factor = match iteration_nodes.windows(2).map(|w| w[1] - w[0]).collect::<Vec<_>>().as_slice() {
+            [.., prev, last] if *prev > 0 => *last as f64 / *prev as f64,
+            _ => 0.0
+        };
+
+        SearchResult {
+            score,
+            pv: moves.iter().rev().cloned().collect(),
+            depth,
+            seldepth: ctx.stats.seldepth,
+            nodes: ctx.nodes,
+            qnodes: ctx.stats.qnodes,
+            time: start.elapsed(),
+            tt_hits: ctx.stats.tt_hits,
+            tb_hits: ctx.stats.tb_hits,
+            beta_cutoffs: ctx.stats.beta_cutoffs,
+            first_move_cutoffs: ctx.stats.first_move_cutoffs,
+            branching_factor
+        }
+    }
+
+    pub fn best_move(&self) -> Option<&Move> {
+        self.pv.first()
+    }
+
+    pub fn nps(&self) -> u64 {
+        (self.nodes as f64 / self.time.as_secs_f64().max(0.001)) as u64
+    }
+
+    /// The share of beta cutoffs that came on the first move searched
+    pub fn first_move_rate(&self) -> f64 {
+        if self.beta_cutoffs == 0 {
+            0.0
+        } else {
+            self.first_move_cutoffs as f64 / self.beta_cutoffs as f64
+        }
+    }
+
+    fn pv_uci(&self) -> Vec<String> {
+        self.pv.iter().map(|mv| mv.to_uci(CastlingMode::Standard).to_string()).collect()
+    }
+
+    /// A single line, in the same order and style as UCI info
+    pub fn to_text(&self) -> String {
+        format!("depth {} seldepth {} score {} nodes {} qnodes {} nps {} time {} tthits {} tbhits {} cutoffs {} first {:.1}% ebf {:.2} pv {}",
+                self.depth,
+                self.seldepth,
+                self.score,
+                self.nodes,
+                self.qnodes,
+                self.nps(),
+                self.time.as_millis(),
+                self.tt_hits,
+                self.tb_hits,
+                self.beta_cutoffs,
+                self.first_move_rate() * 100.0,
+                self.branching_factor,
+                self.pv_uci().join(" "))
+    }
+
+    /// A single line JSON object, the score is either {"cp": n} or {"mate": n} in moves
+    pub fn to_json(&self) -> String {
+        let score = match self.score.mate_moves() {
+            Some(moves) => format!("{{\"mate\":{}}}", moves),
+            None => format!("{{\"cp\":{}}}", self.score.raw())
+        };
+        let pv = self.pv_uci().iter().map(|mv| format!("\"{}\"", mv)).collect::<Vec<_>>().join(",");
+
+        format!("{{\"score\":{},\"pv\":[{}],\"depth\":{},\"seldepth\":{},\"nodes\":{},\"qnodes\":{},\"nps\":{},\"time_ms\":{},\"tt_hits\":{},\"tb_hits\":{},\"beta_cutoffs\":{},\"first_move_cutoff_rate\":{:.4},\"branching_factor\":{:.4}}}",
+                score,
+                pv,
+                self.depth,
+                self.seldepth,
+                self.nodes,
+                self.qnodes,
+                self.nps(),
+                self.time.as_millis(),
+                self.tt_hits,
+                self.tb_hits,
+                self.beta_cutoffs,
+                self.first_move_rate(),
+                self.branching_factor)
+    }
+}
+
+
+#[cfg(test)]
+mod result_tests {
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+
+    use shakmaty::{Chess, CastlingMode};
+    use shakmaty::fen::Fen;
+
+    use crate::evaluator::PstEvaluator;
+    use crate::result::SearchResult;
+    use crate::search::{SearchContext, SearchLimits, SearchOptions, Algorithm};
+    use crate::tt::TranspositionTable;
+
+    #[test]
+    fn result_test() {
+        let tt = TranspositionTable::new(1);
+        let eval = PstEvaluator::default();
+        let limits = SearchLimits { depth: Some(4), ..SearchLimits::default() };
+
+        let mut ctx = SearchContext::new(Arc::new(AtomicBool::new(false)), &tt, &eval, SearchOptions::default());
+        let result = SearchResult::search(&mut ctx, 1, Algorithm::Pvs, &Chess::default(), &limits);
+
+        assert_eq!(result.depth, 4);
+        assert!(result.seldepth >= 4);
+        assert!(result.qnodes > 0 && result.qnodes < result.nodes);
+        assert!(result.first_move_rate() > 0.5 && result.first_move_rate() <= 1.0);
+        assert!(result.branching_factor > 1.0);
+        assert_eq!(result.best_move(), result.pv.first());
+        assert!(result.to_text().starts_with("depth 4 seldepth "));
+        assert!(result.to_json().starts_with("{\"score\":{\"cp\":"));
+
+        // mate in 1
+        let game :Chess = "7k/8/6K1/5Q2/8/8/8/8 w - - 0 1".parse::<Fen>().unwrap().position(CastlingMode::Standard).unwrap();
+        let mut ctx = SearchContext::new(Arc::new(AtomicBool::new(false)), &tt, &eval, SearchOptions::default());
+        let result = SearchResult::search(&mut ctx, 1, Algorithm::AlphaBeta, &game, &limits);
+
+        assert!(result.to_json().starts_with("{\"score\":{\"mate\":1},\"pv\":[\"f5"));
+    }
+}
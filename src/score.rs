@@ -0,0 +1,167 @@
+use std::fmt;
+use std::ops::{Add, Neg, Sub};
+
+/// Mates are scored as MATE minus the number of plies from the root to the mate, so shorter mates score higher
+const MATE :i32 = 30_000;
+
+/// No search gets this deep, so any score within MAX_PLY of MATE is a mate
+const MAX_PLY :i32 = 256;
+
+/// Centipawn scores are kept below the mate scores
+const MAX_CENTIPAWNS :i32 = MATE - MAX_PLY - 1;
+
+/// Tablebase wins are centipawn scores, but bigger than any evaluation, so a known win beats any material advantage
+const TB_WIN :i32 = MAX_CENTIPAWNS - MAX_PLY;
+
+/// A score from the side to move's point of view: either centipawns, or mate in so many plies from the root
+///
+/// Scores are symmetric around zero, so negating one always gives the opponent's view of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Score(i32);
+
+impl Score {
+    /// Above any score a search can return, for the initial window
+    pub const INFINITY :Score = Score(MATE + 1);
+    pub const DRAW :Score = Score(0);
+
+    /// A centipawn score, clamped so it can't be mistaken for a mate
+    pub fn cp(centipawns :i64) -> Self {
+        Score(centipawns.clamp(-MAX_CENTIPAWNS as i64, MAX_CENTIPAWNS as i64) as i32)
+    }
+
+    /// The side to move mates, ply plies from the root
+    pub fn mate_in(ply :usize) -> Self {
+        Score(MATE - ply as i32)
+    }
+
+    /// The side to move is mated, ply plies from the root
+    pub fn mated_in(ply :usize) -> Self {
+        Score(-MATE + ply as i32)
+    }
+
+    /// The tablebases say the side to move wins, found ply plies from the root
+    pub fn tb_win_in(ply :usize) -> Self {
+        Score(TB_WIN - ply as i32)
+    }
+
+    /// The tablebases say the side to move loses, found ply plies from the root
+    pub fn tb_loss_in(ply :usize) -> Self {
+        Score(-TB_WIN + ply as i32)
+    }
+
+    pub fn is_mate(&self) -> bool {
+        self.0.abs() > MAX_CENTIPAWNS && self.0.abs() <= MATE
+    }
+
+    /// Plies from the root to the mate, positive when the side to move mates
+    pub fn mate_plies(&self) -> Option<i32> {
+        if !self.is_mate() {
+            None
+        } else if self.0 > 0 {
+            Some(MATE - self.0)
+        } else {
+            Some(-(MATE + self.0))
+        }
+    }
+
+    /// Full moves to the mate like UCI reports them, positive when the side to move mates
+    pub fn mate_moves(&self) -> Option<i32> {
+        self.mate_plies().map(|plies| if plies > 0 { (plies + 1) / 2 } else { plies / 2 })
+    }
+
+    /// The score in centipawns, mates are just very big numbers
+    pub fn raw(&self) -> i32 {
+        self.0
+    }
+
+    /// The inverse of raw, for scores that were stored as a number
+    pub fn from_raw(raw :i32) -> Self {
+        Score(raw.clamp(-Score::INFINITY.0, Score::INFINITY.0))
+    }
+
+    /// Mates are stored in the transposition table as distance from the node instead of the root,
+    /// as the same position can be reached at different plies
+    pub fn relative_to_node(self, ply :usize) -> Self {
+        match self.mate_plies() {
+            Some(plies) if plies > 0 => Score(self.0 + ply as i32),
+            Some(_) => Score(self.0 - ply as i32),
+            None => self
+        }
+    }
+
+    /// The inverse of relative_to_node, for a score read back from the table at this ply
+    pub fn relative_to_root(self, ply :usize) -> Self {
+        if self.0 > MAX_CENTIPAWNS {
+            Score(self.0 - ply as i32)
+        } else if self.0 < -MAX_CENTIPAWNS {
+            Score(self.0 + ply as i32)
+        } else {
+            self
+        }
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+
+    fn neg(self) -> Score {
+        Score(-self.0)
+    }
+}
+
+/// Moving a score by some centipawns, used for windows and margins, stays within -INFINITY..INFINITY
+impl Add<i32> for Score {
+    type Output = Score;
+
+    fn add(self, centipawns :i32) -> Score {
+        Score(self.0.saturating_add(centipawns).clamp(-Score::INFINITY.0, Score::INFINITY.0))
+    }
+}
+
+impl Sub<i32> for Score {
+    type Output = Score;
+
+    fn sub(self, centipawns :i32) -> Score {
+        Score(self.0.saturating_sub(centipawns).clamp(-Score::INFINITY.0, Score::INFINITY.0))
+    }
+}
+
+/// In UCI notation, "cp 25" or "mate -3"
+impl fmt::Display for Score {
+    fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+        match self.mate_moves() {
+            Some(moves) => write!(f, "mate {}", moves),
+            None => write!(f, "cp {}", self.0)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod score_tests {
+    use crate::score::Score;
+
+    #[test]
+    fn mate_test() {
+        // mate in 3 moves is 5 plies away
+        assert_eq!(Score::mate_in(5).mate_moves(), Some(3));
+        assert_eq!((-Score::mate_in(5)).mate_moves(), Some(-2));
+        assert_eq!(Score::mated_in(4).mate_moves(), Some(-2));
+
+        assert!(Score::mate_in(1) > Score::mate_in(3));
+        assert!(Score::mated_in(1) < Score::mated_in(3));
+        assert!(Score::cp(i64::MAX) < Score::mate_in(200));
+        assert!(!Score::cp(i64::MIN).is_mate());
+
+        // a tablebase win isn't a mate, but beats any evaluation
+        assert!(!Score::tb_win_in(3).is_mate());
+        assert!(Score::tb_win_in(3) < Score::mate_in(200));
+        assert!(Score::tb_win_in(3) > Score::cp(10_000));
+        assert_eq!(-Score::tb_win_in(3), Score::tb_loss_in(3));
+
+        // stored relative to the node, and read back at a different ply
+        assert_eq!(Score::mate_in(7).relative_to_node(4).relative_to_root(2), Score::mate_in(5));
+        assert_eq!(Score::mated_in(7).relative_to_node(4).relative_to_root(2), Score::mated_in(5));
+        assert_eq!(Score::cp(50).relative_to_node(4).relative_to_root(2), Score::cp(50));
+    }
+}
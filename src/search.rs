@@ -0,0 +1,1270 @@
+use std::cmp::{max, min};
+use std::sync::Arc;
+use std::ops::AddAssign;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Instant;
+
+use shakmaty::{Chess, Position, Setup, Move};
+
+use smallvec::{smallvec, SmallVec};
+
+use crate::eval::piece_value;
+use crate::evaluator::Evaluator;
+use crate::score::Score;
+use crate::movepick::{MovePicker, OrderingTables, is_noisy, mvv_lva, sort_by_score};
+use crate::tablebase::Tablebase;
+use crate::time::TimeBudget;
+use crate::tt::{TranspositionTable, Bound, PackedMove};
+use crate::zobrist;
+
+
+pub const MAX_DEPTH :usize = 96;
+
+/// A capture has to be able to bring us this close to alpha to be searched in quiescence
+const DELTA_MARGIN :i32 = 200;
+
+/// Past this the aspiration window is given up on, and that side of the window is opened all the way
+const MAX_ASPIRATION_WINDOW :i32 = 1000;
+
+/// How often (in nodes) we check the clock and the stop flag, must be a power of 2
+const CHECK_INTERVAL :u64 = 1024;
+
+/// negamax_ab and negamax_pvs, so the helpers can call back into whichever one is running
+type SearchFn<E> = fn(&mut SearchContext<E>, &Chess, usize, usize, &mut Score, Score) -> (Score, SmallVec<[Move; MAX_DEPTH]>);
+
+/// The search algorithms we know how to run, PVS is what we play with unless asked otherwise
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Basic,
+    AlphaBeta,
+    #[default]
+    Pvs
+}
+
+impl Algorithm {
+    pub const ALL :[Algorithm; 3] = [Algorithm::Basic, Algorithm::AlphaBeta, Algorithm::Pvs];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Algorithm::Basic => "basic",
+            Algorithm::AlphaBeta => "ab",
+            Algorithm::Pvs => "pvs"
+        }
+    }
+
+    pub fn from_name(name :&str) -> Option<Algorithm> {
+        match name.to_lowercase().as_str() {
+            "basic" => Some(Algorithm::Basic),
+            "ab" => Some(Algorithm::AlphaBeta),
+            "pvs" => Some(Algorithm::Pvs),
+            _ => None
+        }
+    }
+}
+
+/// Switches for search features, so they can be measured against each other
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    /// Search captures and promotions at the horizon instead of returning the static eval
+    pub quiescence: bool,
+    /// Also search quiet checking moves at the first ply of the quiescence search
+    pub qs_checks: bool,
+    /// Skip quiescence captures that can't get back to alpha, this depends on the window so it can change the score
+    pub delta_pruning: bool,
+    /// Use static exchange evaluation to try losing captures after the quiet moves
+    pub see: bool,
+    /// Let the opponent move twice, if they still can't get below beta our position is good enough to cut
+    pub null_move: bool,
+    /// How many plies shallower than a normal move the null move is searched
+    pub null_move_reduction: usize,
+    /// Search late quiet moves at a reduced depth, and only re-search the ones that beat alpha
+    pub lmr: bool,
+    /// How many moves are searched at full depth before we start reducing
+    pub lmr_full_moves: usize,
+    /// Nodes with less depth left than this are never reduced
+    pub lmr_min_depth: usize,
+    /// How many plies a late move is reduced by, moves later than twice lmr_full_moves get one more
+    pub lmr_reduction: usize,
+    /// Search each iteration with a narrow window around the previous score, widening it when the score falls outside
+    pub aspiration: bool,
+    /// How far the first window reaches either side of the previous score, it doubles every time it fails
+    pub aspiration_window: i32,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            quiescence: true,
+            qs_checks: false,
+            delta_pruning: true,
+            see: true,
+            null_move: true,
+            null_move_reduction: 2,
+            lmr: true,
+            lmr_full_moves: 3,
+            lmr_min_depth: 3,
+            lmr_reduction: 1,
+            aspiration: true,
+            aspiration_window: 25
+        }
+    }
+}
+
+/// Counters describing how the search went
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SearchStats {
+    pub beta_cutoffs: u64,
+    /// Cutoffs caused by the first move searched, a measure of how good the move ordering is
+    pub first_move_cutoffs: u64,
+    /// Moves that failed high on the null window, and had to be searched again
+    pub pvs_researches: u64,
+    /// Null moves that failed high, and so cut the node without searching any real moves
+    pub null_cutoffs: u64,
+    /// Null move cutoffs in zugzwang-prone positions that a normal search didn't confirm
+    pub null_verify_fails: u64,
+    pub lmr_reductions: u64,
+    /// Reduced moves that beat alpha, and had to be searched again at full depth
+    pub lmr_researches: u64,
+    /// Root searches that came back at or below the aspiration window
+    pub aspiration_fail_lows: u64,
+    /// Root searches that came back at or above the aspiration window
+    pub aspiration_fail_highs: u64,
+    pub tt_hits: u64,
+    pub tt_misses: u64,
+    pub tt_stores: u64,
+    /// Positions scored by the endgame tablebases instead of being searched
+    pub tb_hits: u64,
+    /// Nodes searched by quiesce, these are counted in the nodes as well
+    pub qnodes: u64,
+    /// The deepest ply any node was searched at, quiescence included
+    pub seldepth: usize,
+}
+
+impl AddAssign for SearchStats {
+    fn add_assign(&mut self, other :SearchStats) {
+        self.beta_cutoffs += other.beta_cutoffs;
+        self.first_move_cutoffs += other.first_move_cutoffs;
+        self.pvs_researches += other.pvs_researches;
+        self.null_cutoffs += other.null_cutoffs;
+        self.null_verify_fails += other.null_verify_fails;
+        self.lmr_reductions += other.lmr_reductions;
+        self.lmr_researches += other.lmr_researches;
+        self.aspiration_fail_lows += other.aspiration_fail_lows;
+        self.aspiration_fail_highs += other.aspiration_fail_highs;
+        self.tt_hits += other.tt_hits;
+        self.tt_misses += other.tt_misses;
+        self.tt_stores += other.tt_stores;
+        self.tb_hits += other.tb_hits;
+        self.qnodes += other.qnodes;
+        self.seldepth = max(self.seldepth, other.seldepth);
+    }
+}
+
+/// What we were asked to search: all limits are optional, and the first one reached ends the search
+#[derive(Debug, Default, Clone)]
+pub struct SearchLimits {
+    pub depth: Option<usize>,
+    pub nodes: Option<u64>,
+    pub time: Option<TimeBudget>,
+    pub infinite: bool,
+}
+
+/// Book-keeping for a single search: node count, the transposition table, the evaluator, and the conditions that end it early
+pub struct SearchContext<'a, E :Evaluator> {
+    pub nodes: u64,
+    pub tt: &'a TranspositionTable,
+    pub eval: &'a E,
+    pub options: SearchOptions,
+    pub stats: SearchStats,
+    tablebase: Option<&'a Tablebase>,
+    ordering: OrderingTables,
+    node_limit: Option<u64>,
+    deadline: Option<Instant>,
+    prev_pv: Vec<Move>,
+    follow_pv: bool,
+    after_null: bool,
+    history: Vec<u64>,
+    stop: Arc<AtomicBool>,
+    stopped: bool,
+    shared_nodes: Option<Arc<AtomicU64>>,
+}
+
+impl<'a, E :Evaluator> SearchContext<'a, E> {
+    pub fn new(stop :Arc<AtomicBool>, tt :&'a TranspositionTable, eval :&'a E, options :SearchOptions) -> Self {
+        SearchContext {
+            nodes: 0,
+            tt,
+            eval,
+            options,
+            stats: SearchStats::default(),
+            tablebase: None,
+            ordering: OrderingTables::new(),
+            node_limit: None,
+            deadline: None,
+            prev_pv: Vec::new(),
+            follow_pv: false,
+            after_null: false,
+            history: Vec::new(),
+            stop,
+            stopped: false,
+            shared_nodes: None
+        }
+    }
+
+    pub fn set_node_limit(&mut self, node_limit :Option<u64>) {
+        self.node_limit = node_limit;
+    }
+
+    pub fn set_deadline(&mut self, deadline :Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    /// Endgame tables to score positions with few enough pieces, instead of searching them
+    pub fn set_tablebase(&mut self, tablebase :Option<&'a Tablebase>) {
+        self.tablebase = tablebase;
+    }
+
+    pub fn tablebase(&self) -> Option<&'a Tablebase> {
+        self.tablebase
+    }
+
+    /// True if the search was aborted, the results of an aborted search cannot be trusted
+    pub fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// The hashes of the positions played before the root, oldest first, so we can see repetitions
+    pub fn set_history(&mut self, history :Vec<u64>) {
+        self.history = history;
+    }
+
+    pub fn history(&self) -> &[u64] {
+        &self.history
+    }
+
+    /// Adds our nodes to a counter shared by all the threads searching the same position
+    pub fn set_shared_nodes(&mut self, shared_nodes :Option<Arc<AtomicU64>>) {
+        self.shared_nodes = shared_nodes;
+    }
+
+    /// The nodes searched by all threads, the other threads only report theirs every CHECK_INTERVAL nodes
+    pub fn total_nodes(&self) -> u64 {
+        match &self.shared_nodes {
+            Some(shared) => shared.load(Ordering::Relaxed) + (self.nodes & (CHECK_INTERVAL - 1)),
+            None => self.nodes
+        }
+    }
+
+    /// Reports the nodes we haven't added to the shared counter yet, called when the thread is done
+    pub fn flush_nodes(&mut self) {
+        if let Some(shared) = &self.shared_nodes {
+            shared.fetch_add(self.nodes & (CHECK_INTERVAL - 1), Ordering::Relaxed);
+        }
+    }
+
+    /// Counts the node, and checks if we should abort the search
+    #[inline]
+    fn visit(&mut self, ply :usize) -> bool {
+        self.nodes += 1;
+        self.stats.seldepth = max(self.stats.seldepth, ply);
+
+        if let Some(limit) = self.node_limit {
+            if self.nodes >= limit {
+                self.stopped = true;
+            }
+        }
+
+        // only look at the clock & flag every so often
+        if self.nodes & (CHECK_INTERVAL - 1) == 0 {
+            if let Some(shared) = &self.shared_nodes {
+                shared.fetch_add(CHECK_INTERVAL, Ordering::Relaxed);
+            }
+
+            if self.stop.load(Ordering::Relaxed) {
+                self.stopped = true;
+            }
+
+            if let Some(deadline) = self.deadline {
+                if Instant::now() >= deadline {
+                    self.stopped = true;
+                }
+            }
+        }
+
+        self.stopped
+    }
+}
+
+/// Searches depth 1, 2, 3, ... until a limit is reached, calling on_iteration after each completed depth
+/// The result is the score and PV of the last completed iteration, an aborted iteration is thrown away
+pub fn iterative_deepening<E :Evaluator, F>(ctx :&mut SearchContext<E>, algorithm :Algorithm, game :&Chess, limits :&SearchLimits, on_iteration :F) -> (Score, SmallVec<[Move; MAX_DEPTH]>)
+    where F: FnMut(&SearchContext<E>, usize, Score, &SmallVec<[Move; MAX_DEPTH]>)
+{
+    deepen(ctx, algorithm, game, limits, 1, on_iteration)
+}
+
+/// Iterative deepening starting at first_depth, helper threads start at different depths so they don't all search the same tree
+pub fn deepen<E :Evaluator, F>(ctx :&mut SearchContext<E>, algorithm :Algorithm, game :&Chess, limits :&SearchLimits, first_depth :usize, mut on_iteration :F) -> (Score, SmallVec<[Move; MAX_DEPTH]>)
+    where F: FnMut(&SearchContext<E>, usize, Score, &SmallVec<[Move; MAX_DEPTH]>)
+{
+    let start = Instant::now();
+    let deadlines = limits.time.map(|t| t.deadlines(start));
+
+    ctx.set_node_limit(limits.nodes);
+    ctx.set_deadline(deadlines.map(|d| d.hard));
+
+    let mut best = (Score::DRAW, smallvec![]);
+
+    for depth in first_depth..=limits.depth.unwrap_or(MAX_DEPTH).min(MAX_DEPTH) {
+        let (score, moves) = match best {
+            (prev_score, ref prev_moves) if !prev_moves.is_empty() => aspiration_search(ctx, algorithm, game, depth, prev_score),
+            _ => search(ctx, algorithm, game, depth)
+        };
+
+        // a partial iteration cannot be trusted, and no moves means the game is over
+        if ctx.stopped() || moves.is_empty() {
+            break
+        }
+
+        on_iteration(ctx, depth, score, &moves);
+
+        // seed the move ordering of the next iteration with this PV, the root move is last
+        ctx.prev_pv = moves.iter().rev().cloned().collect();
+        best = (score, moves);
+
+        // the next iteration won't finish in time, so don't bother starting it
+        if let Some(deadlines) = deadlines {
+            if Instant::now() >= deadlines.soft {
+                break
+            }
+        }
+    }
+
+    best
+}
+
+/// Runs the given algorithm to a fixed depth
+pub fn search<E :Evaluator>(ctx :&mut SearchContext<E>, algorithm :Algorithm, game :&Chess, depth :usize) -> (Score, SmallVec<[Move; MAX_DEPTH]>) {
+    search_window(ctx, algorithm, game, depth, -Score::INFINITY, Score::INFINITY)
+}
+
+/// Runs the given algorithm to a fixed depth with the window alpha..beta at the root, basic ignores the window
+fn search_window<E :Evaluator>(ctx :&mut SearchContext<E>, algorithm :Algorithm, game :&Chess, depth :usize, mut alpha :Score, beta :Score) -> (Score, SmallVec<[Move; MAX_DEPTH]>) {
+    ctx.follow_pv = true;
+
+    match algorithm {
+        Algorithm::Basic => negamax_basic(ctx, game, depth, 0),
+        Algorithm::AlphaBeta => negamax_ab(ctx, game, depth, 0, &mut alpha, beta),
+        Algorithm::Pvs => negamax_pvs(ctx, game, depth, 0, &mut alpha, beta)
+    }
+}
+
+/// Searches with a window around the previous iteration's score, as the score usually doesn't move much
+/// between iterations and a narrow window cuts a lot more. When the score lands on or outside the window
+/// it's only a bound, so that side of the window is widened and the depth searched again.
+fn aspiration_search<E :Evaluator>(ctx :&mut SearchContext<E>, algorithm :Algorithm, game :&Chess, depth :usize, prev_score :Score) -> (Score, SmallVec<[Move; MAX_DEPTH]>) {
+    // a mate score won't move by a few centipawns
+    if !ctx.options.aspiration || algorithm == Algorithm::Basic || prev_score.is_mate() {
+        return search(ctx, algorithm, game, depth);
+    }
+
+    let mut delta = ctx.options.aspiration_window.max(1);
+    let mut alpha = prev_score - delta;
+    let mut beta = prev_score + delta;
+
+    loop {
+        let (score, moves) = search_window(ctx, algorithm, game, depth, alpha, beta);
+
+        if ctx.stopped() {
+            return (score, moves);
+        }
+
+        delta = delta.saturating_mul(2);
+
+        if score <= alpha && alpha > -Score::INFINITY {
+            ctx.stats.aspiration_fail_lows += 1;
+            alpha = if delta > MAX_ASPIRATION_WINDOW { -Score::INFINITY } else { score - delta };
+        } else if score >= beta && beta < Score::INFINITY {
+            ctx.stats.aspiration_fail_highs += 1;
+            beta = if delta > MAX_ASPIRATION_WINDOW { Score::INFINITY } else { score + delta };
+        } else {
+            return (score, moves);
+        }
+    }
+}
+
+pub fn negamax_ab<E :Evaluator>(ctx :&mut SearchContext<E>, game :&Chess, depth :usize, ply :usize, alpha :&mut Score, beta :Score) -> (Score, SmallVec<[Move; MAX_DEPTH]>) {
+    let after_null = std::mem::take(&mut ctx.after_null);
+
+    if ctx.visit(ply) {
+        return (Score::DRAW, smallvec![]);
+    }
+
+    let key = zobrist::hash(game);
+
+    if ply > 0 && is_draw(ctx, game, key) {
+        return (Score::DRAW, smallvec![]);
+    }
+
+    if depth == 0 {
+        return (horizon(ctx, game, ply, *alpha, beta), smallvec![]);
+    }
+
+    // mate distance pruning: not even mating right away can beat a shorter mate found elsewhere
+    let beta = if ply > 0 { min(beta, Score::mate_in(ply + 1)) } else { beta };
+
+    if ply > 0 {
+        *alpha = max(*alpha, Score::mated_in(ply));
+
+        if *alpha >= beta {
+            return (*alpha, smallvec![]);
+        }
+    }
+
+    let alpha_orig = *alpha;
+
+    let (hash_move, cutoff) = probe_tt(ctx, key, depth, ply, *alpha, beta);
+
+    if let Some(score) = cutoff {
+        return (score, hash_move.and_then(|mv| mv.unpack(game)).into_iter().collect());
+    }
+
+    if let Some(score) = probe_tablebase(ctx, game, ply) {
+        return (score, smallvec![]);
+    }
+
+    let in_check = game.is_check();
+
+    if let Some(score) = null_move(ctx, game, key, depth, ply, beta, in_check, after_null, negamax_ab) {
+        return (score, smallvec![]);
+    }
+
+    let mut value = -Score::INFINITY;
+    let mut stack = smallvec![];
+
+    ctx.history.push(key);
+
+    let mut picker = MovePicker::new(game, first_move(ctx, ply, hash_move), ply, ctx.options.see);
+    let mut move_count = 0;
+
+    while let Some(mv) = picker.next(game, &ctx.ordering) {
+        move_count += 1;
+
+        let mut new_game = game.clone();
+        new_game.play_unchecked(&mv);
+
+        let reduction = lmr_reduction(ctx, depth, move_count, &mv, in_check, &new_game);
+        let mut reduced = None;
+
+        if reduction > 0 {
+            // a null window is enough to find out if the move beats alpha
+            ctx.stats.lmr_reductions += 1;
+
+            let (v, s) = search_move(ctx, negamax_ab, &new_game, depth - 1 - reduction, ply, *alpha, *alpha + 1);
+
+            if v > *alpha && !ctx.stopped() {
+                ctx.stats.lmr_researches += 1;
+            } else {
+                reduced = Some((v, s));
+            }
+        }
+
+        // make the recursive call
+        let (new_value, new_stack) = match reduced {
+            Some(result) => result,
+            None => search_move(ctx, negamax_ab, &new_game, depth - 1, ply, *alpha, beta)
+        };
+
+        // only the first move searched can be on the previous PV
+        ctx.follow_pv = false;
+
+        if ctx.stopped() {
+            ctx.history.pop();
+            return (Score::DRAW, smallvec![]);
+        }
+
+        if new_value > value {
+            stack = new_stack;
+            stack.push(mv.clone());
+            value = new_value;
+        }
+
+        *alpha = max(*alpha, value);
+
+        if *alpha >= beta {
+            record_cutoff(ctx, game, &mv, move_count, ply, depth);
+            break
+        }
+    }
+
+    ctx.history.pop();
+
+    // no legal moves, so it's either mate or stalemate
+    if move_count == 0 {
+        return (if in_check { Score::mated_in(ply) } else { Score::DRAW }, smallvec![]);
+    }
+
+    store_tt(ctx, key, depth, ply, alpha_orig, beta, value, &stack);
+
+    (value, stack)
+}
+
+/// Principal Variation Search (NegaScout): assumes the first move is the best, and only tries to prove
+/// the others are worse with a null window around alpha. A move that fails high is searched again with
+/// the full window, as it might be a new best move.
+pub fn negamax_pvs<E :Evaluator>(ctx :&mut SearchContext<E>, game :&Chess, depth :usize, ply :usize, alpha :&mut Score, beta :Score) -> (Score, SmallVec<[Move; MAX_DEPTH]>) {
+    let after_null = std::mem::take(&mut ctx.after_null);
+
+    if ctx.visit(ply) {
+        return (Score::DRAW, smallvec![]);
+    }
+
+    let key = zobrist::hash(game);
+
+    if ply > 0 && is_draw(ctx, game, key) {
+        return (Score::DRAW, smallvec![]);
+    }
+
+    if depth == 0 {
+        return (horizon(ctx, game, ply, *alpha, beta), smallvec![]);
+    }
+
+    // mate distance pruning: not even mating right away can beat a shorter mate found elsewhere
+    let beta = if ply > 0 { min(beta, Score::mate_in(ply + 1)) } else { beta };
+
+    if ply > 0 {
+        *alpha = max(*alpha, Score::mated_in(ply));
+
+        if *alpha >= beta {
+            return (*alpha, smallvec![]);
+        }
+    }
+
+    let alpha_orig = *alpha;
+
+    let (hash_move, cutoff) = probe_tt(ctx, key, depth, ply, *alpha, beta);
+
+    if let Some(score) = cutoff {
+        return (score, hash_move.and_then(|mv| mv.unpack(game)).into_iter().collect());
+    }
+
+    if let Some(score) = probe_tablebase(ctx, game, ply) {
+        return (score, smallvec![]);
+    }
+
+    let in_check = game.is_check();
+
+    if let Some(score) = null_move(ctx, game, key, depth, ply, beta, in_check, after_null, negamax_pvs) {
+        return (score, smallvec![]);
+    }
+
+    let mut value = -Score::INFINITY;
+    let mut stack = smallvec![];
+
+    ctx.history.push(key);
+
+    let mut picker = MovePicker::new(game, first_move(ctx, ply, hash_move), ply, ctx.options.see);
+    let mut move_count = 0;
+
+    while let Some(mv) = picker.next(game, &ctx.ordering) {
+        move_count += 1;
+
+        let mut new_game = game.clone();
+        new_game.play_unchecked(&mv);
+
+        let (new_value, new_stack) = if move_count == 1 {
+            // the first move gets the full window
+            let result = search_move(ctx, negamax_pvs, &new_game, depth - 1, ply, *alpha, beta);
+
+            ctx.follow_pv = false;
+            result
+        } else {
+            // prove this move is no better than alpha with a null window, late quiet moves at a reduced depth
+            let reduction = lmr_reduction(ctx, depth, move_count, &mv, in_check, &new_game);
+            let (mut v, mut s) = search_move(ctx, negamax_pvs, &new_game, depth - 1 - reduction, ply, *alpha, *alpha + 1);
+
+            if reduction > 0 {
+                ctx.stats.lmr_reductions += 1;
+
+                if v > *alpha && !ctx.stopped() {
+                    ctx.stats.lmr_researches += 1;
+
+                    (v, s) = search_move(ctx, negamax_pvs, &new_game, depth - 1, ply, *alpha, *alpha + 1);
+                }
+            }
+
+            if v > *alpha && v < beta && !ctx.stopped() {
+                // it's better, so find out by how much
+                ctx.stats.pvs_researches += 1;
+
+                (v, s) = search_move(ctx, negamax_pvs, &new_game, depth - 1, ply, *alpha, beta);
+            }
+
+            (v, s)
+        };
+
+        if ctx.stopped() {
+            ctx.history.pop();
+            return (Score::DRAW, smallvec![]);
+        }
+
+        if new_value > value {
+            stack = new_stack;
+            stack.push(mv.clone());
+            value = new_value;
+        }
+
+        *alpha = max(*alpha, value);
+
+        if *alpha >= beta {
+            record_cutoff(ctx, game, &mv, move_count, ply, depth);
+            break
+        }
+    }
+
+    ctx.history.pop();
+
+    // no legal moves, so it's either mate or stalemate
+    if move_count == 0 {
+        return (if in_check { Score::mated_in(ply) } else { Score::DRAW }, smallvec![]);
+    }
+
+    store_tt(ctx, key, depth, ply, alpha_orig, beta, value, &stack);
+
+    (value, stack)
+}
+
+/// Searches the position after one of our moves with the window alpha..beta, the score is from our side
+#[inline]
+fn search_move<E :Evaluator>(ctx :&mut SearchContext<E>, search :SearchFn<E>, new_game :&Chess, depth :usize, ply :usize, alpha :Score, beta :Score) -> (Score, SmallVec<[Move; MAX_DEPTH]>) {
+    let mut new_alpha = -beta;
+    let (value, stack) = search(ctx, new_game, depth, ply + 1, &mut new_alpha, -alpha);
+
+    (-value, stack)
+}
+
+/// Null move pruning: if we pass and the opponent still can't get below beta, one of our real moves will do
+/// even better, so we can return without searching them. Returns the score to cut with.
+///
+/// With only pawns left we might be in zugzwang, where passing would be our best move if it were allowed,
+/// so there the cutoff is only trusted when a normal search at the reduced depth agrees.
+#[allow(clippy::too_many_arguments)]
+fn null_move<E :Evaluator>(ctx :&mut SearchContext<E>, game :&Chess, key :u64, depth :usize, ply :usize, beta :Score, in_check :bool, after_null :bool, search :SearchFn<E>) -> Option<Score> {
+    let reduction = ctx.options.null_move_reduction;
+
+    // never two null moves in a row, and not on the PV where we want the exact score
+    if !ctx.options.null_move || after_null || in_check || ctx.follow_pv || ply == 0 || depth <= reduction || beta.is_mate() {
+        return None;
+    }
+
+    if static_eval(ctx, game) < beta {
+        return None;
+    }
+
+    let null_game = game.clone().swap_turn().ok()?;
+
+    // passing is a move like any other, so the positions after it can repeat this one
+    ctx.history.push(key);
+    ctx.after_null = true;
+    let (value, _) = search_move(ctx, search, &null_game, depth - 1 - reduction, ply, beta - 1, beta);
+    ctx.history.pop();
+
+    if value < beta || ctx.stopped() {
+        return None;
+    }
+
+    if is_zugzwang_prone(game) {
+        // the verification search shouldn't try the null move again at this node
+        let mut alpha = beta - 1;
+
+        ctx.after_null = true;
+        let (value, _) = search(ctx, game, depth - reduction, ply, &mut alpha, beta);
+
+        if value < beta || ctx.stopped() {
+            ctx.stats.null_verify_fails += 1;
+            return None;
+        }
+    }
+
+    ctx.stats.null_cutoffs += 1;
+
+    // we can't claim a mate we found by passing
+    Some(if value.is_mate() { beta } else { value })
+}
+
+/// True when the side to move only has pawns, so it might be forced to make its position worse
+fn is_zugzwang_prone(game :&Chess) -> bool {
+    let board = game.board();
+
+    (board.by_color(game.turn()) & !board.pawns() & !board.kings()).is_empty()
+}
+
+/// How many plies shallower to search a move: quiet moves late in the ordering are unlikely to be best
+fn lmr_reduction<E :Evaluator>(ctx :&SearchContext<E>, depth :usize, move_count :usize, mv :&Move, in_check :bool, new_game :&Chess) -> usize {
+    let options = &ctx.options;
+
+    if !options.lmr || in_check || depth < options.lmr_min_depth || move_count <= options.lmr_full_moves || is_noisy(mv) || new_game.is_check() {
+        return 0;
+    }
+
+    let reduction = if move_count > 2 * options.lmr_full_moves { options.lmr_reduction + 1 } else { options.lmr_reduction };
+
+    // the reduced search can't go below the horizon
+    reduction.min(depth - 1)
+}
+
+/// Draws by the fifty-move rule, insufficient material, or repeating a position from earlier in the game or search
+///
+/// A single repetition is enough: if repeating the position was the best either side could do the first time,
+/// it will be again.
+fn is_draw<E :Evaluator>(ctx :&SearchContext<E>, game :&Chess, key :u64) -> bool {
+    let reversible_plies = game.halfmoves() as usize;
+
+    // mate on the 100th ply still counts
+    if reversible_plies >= 100 && !game.is_checkmate() {
+        return true;
+    }
+
+    if game.is_insufficient_material() {
+        return true;
+    }
+
+    // only positions since the last capture or pawn move, with the same side to move, can be the same
+    ctx.history.iter().rev().take(reversible_plies).skip(1).step_by(2).any(|k| *k == key)
+}
+
+/// Scores the position from the endgame tablebases, if we have them and few enough pieces are left.
+/// Never at the root, where we need a move and not just a score.
+fn probe_tablebase<E :Evaluator>(ctx :&mut SearchContext<E>, game :&Chess, ply :usize) -> Option<Score> {
+    if ply == 0 {
+        return None;
+    }
+
+    let score = ctx.tablebase?.probe_wdl(game, ply)?;
+
+    ctx.stats.tb_hits += 1;
+
+    Some(score)
+}
+
+/// Probes the transposition table, returning the hash move to try first,
+/// and the score to return right away when the entry is deep enough to cut with
+fn probe_tt<E :Evaluator>(ctx :&mut SearchContext<E>, key :u64, depth :usize, ply :usize, alpha :Score, beta :Score) -> (Option<PackedMove>, Option<Score>) {
+    if !ctx.tt.is_enabled() {
+        return (None, None);
+    }
+
+    let entry = match ctx.tt.probe(key) {
+        Some(entry) => entry,
+        None => {
+            ctx.stats.tt_misses += 1;
+            return (None, None);
+        }
+    };
+
+    ctx.stats.tt_hits += 1;
+
+    let score = entry.score.relative_to_root(ply);
+
+    // never cut at the root, we always want a move to play, nor anywhere else on the PV,
+    // as the table only has the first move of the line and we would report a PV cut short
+    let pv_node = beta > alpha + 1;
+
+    if ply > 0 && !pv_node && entry.depth as usize >= depth {
+        let cutoff = match entry.bound {
+            Bound::Exact => true,
+            Bound::Lower => score >= beta,
+            Bound::Upper => score <= alpha
+        };
+
+        if cutoff {
+            return (entry.best_move, Some(score));
+        }
+    }
+
+    (entry.best_move, None)
+}
+
+/// Stores a result in the transposition table, with the bound implied by the window it was searched with
+#[allow(clippy::too_many_arguments)]
+fn store_tt<E :Evaluator>(ctx :&mut SearchContext<E>, key :u64, depth :usize, ply :usize, alpha :Score, beta :Score, value :Score, stack :&SmallVec<[Move; MAX_DEPTH]>) {
+    let bound = if value <= alpha {
+        Bound::Upper
+    } else if value >= beta {
+        Bound::Lower
+    } else {
+        Bound::Exact
+    };
+
+    if ctx.tt.store(key, depth, bound, value.relative_to_node(ply), stack.last()) {
+        ctx.stats.tt_stores += 1;
+    }
+}
+
+/// The move to try first: the one from the previous iteration's PV if we're still on it, otherwise the hash move
+fn first_move<E :Evaluator>(ctx :&SearchContext<E>, ply :usize, hash_move :Option<PackedMove>) -> Option<PackedMove> {
+    let pv_move = if ctx.follow_pv { ctx.prev_pv.get(ply).map(PackedMove::new) } else { None };
+
+    pv_move.or(hash_move)
+}
+
+/// Counts a beta cutoff, and teaches the move ordering about quiet moves that cut
+fn record_cutoff<E :Evaluator>(ctx :&mut SearchContext<E>, game :&Chess, mv :&Move, move_count :usize, ply :usize, depth :usize) {
+    ctx.stats.beta_cutoffs += 1;
+
+    if move_count == 1 {
+        ctx.stats.first_move_cutoffs += 1;
+    }
+
+    // captures are already ordered well
+    if !is_noisy(mv) {
+        ctx.ordering.update(game.turn(), mv, ply, depth);
+    }
+}
+
+pub fn negamax_basic<E :Evaluator>(ctx :&mut SearchContext<E>, game :&Chess, depth :usize, ply :usize) -> (Score, SmallVec<[Move; MAX_DEPTH]>) {
+    if ctx.visit(ply) {
+        return (Score::DRAW, smallvec![]);
+    }
+
+    let key = zobrist::hash(game);
+
+    if ply > 0 && is_draw(ctx, game, key) {
+        return (Score::DRAW, smallvec![]);
+    }
+
+    if depth == 0 {
+        return (horizon(ctx, game, ply, -Score::INFINITY, Score::INFINITY), smallvec![]);
+    }
+
+    let mut value = -Score::INFINITY;
+    let mut stack = smallvec![];
+    let moves = game.legal_moves();
+
+    // no legal moves, so it's either mate or stalemate
+    if moves.is_empty() {
+        return (if game.is_check() { Score::mated_in(ply) } else { Score::DRAW }, smallvec![]);
+    }
+
+    ctx.history.push(key);
+
+    // generate all the legal moves
+    for mv in moves {
+        let mut new_game = game.clone();
+        new_game.play_unchecked(&mv);
+
+        // make the recursive call
+        let (new_value, new_stack) = negamax_basic(ctx, &new_game, depth - 1, ply + 1);
+        let new_value = -new_value;
+
+        if ctx.stopped() {
+            ctx.history.pop();
+            return (Score::DRAW, smallvec![]);
+        }
+
+        // println!("D{} ({}) {}: {}", depth, mv, new_value, moves2string(&new_stack));
+
+        if new_value > value {
+            stack = new_stack;
+            stack.push(mv.clone());
+            value = new_value;
+        }
+    }
+
+    ctx.history.pop();
+
+    (value, stack)
+}
+
+/// The static eval as a score
+#[inline]
+fn static_eval<E :Evaluator>(ctx :&SearchContext<E>, game :&Chess) -> Score {
+    Score::cp(ctx.eval.evaluate(game))
+}
+
+/// The score at the bottom of the main search: either the static eval, or a quiescence search
+#[inline]
+fn horizon<E :Evaluator>(ctx :&mut SearchContext<E>, game :&Chess, ply :usize, alpha :Score, beta :Score) -> Score {
+    if ctx.options.quiescence {
+        quiesce(ctx, game, ply, 0, alpha, beta)
+    } else {
+        static_eval(ctx, game)
+    }
+}
+
+/// Searches captures and promotions until the position is quiet, so we don't stop in the middle of an exchange
+/// qs_ply counts the plies since the horizon, checks are only searched at the first one
+pub fn quiesce<E :Evaluator>(ctx :&mut SearchContext<E>, game :&Chess, ply :usize, qs_ply :usize, mut alpha :Score, beta :Score) -> Score {
+    ctx.stats.qnodes += 1;
+
+    if ctx.visit(ply) {
+        return Score::DRAW;
+    }
+
+    let in_check = game.is_check();
+
+    // when in check every evasion is searched, so there is no standing pat
+    let stand_pat = if in_check { -Score::INFINITY } else { static_eval(ctx, game) };
+
+    if ply >= MAX_DEPTH {
+        return static_eval(ctx, game);
+    }
+
+    if stand_pat >= beta {
+        return stand_pat;
+    }
+
+    alpha = max(alpha, stand_pat);
+
+    let search_checks = ctx.options.qs_checks && qs_ply == 0;
+    let mut value = stand_pat;
+    let mut moves = game.legal_moves();
+
+    // mate or stalemate, we can only tell when we didn't stand pat above
+    if moves.is_empty() {
+        return if in_check { Score::mated_in(ply) } else { Score::DRAW };
+    }
+
+    if !in_check {
+        moves.retain(|mv| is_noisy(mv) || (search_checks && gives_check(game, mv)));
+    }
+
+    // best captures are last
+    let mut moves = moves.into_iter().collect::<Vec<_>>();
+    sort_by_score(&mut moves, mvv_lva);
+
+    for mv in moves.into_iter().rev() {
+        // delta pruning: skip captures that can't get us back to alpha even with a margin
+        if ctx.options.delta_pruning && !in_check && !mv.is_promotion() {
+            let gain = mv.capture().map(piece_value).unwrap_or(0) as i32;
+
+            if stand_pat + (gain + DELTA_MARGIN) <= alpha {
+                continue
+            }
+        }
+
+        let mut new_game = game.clone();
+        new_game.play_unchecked(&mv);
+
+        let new_value = -quiesce(ctx, &new_game, ply + 1, qs_ply + 1, -beta, -alpha);
+
+        if ctx.stopped() {
+            return Score::DRAW;
+        }
+
+        value = max(value, new_value);
+        alpha = max(alpha, value);
+
+        if alpha >= beta {
+            break
+        }
+    }
+
+    value
+}
+
+/// True if playing the move puts the opponent in check
+fn gives_check(game :&Chess, mv :&Move) -> bool {
+    let mut new_game = game.clone();
+    new_game.play_unchecked(mv);
+
+    new_game.is_check()
+}
+
+
+#[cfg(test)]
+mod search_tests {
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+
+    use shakmaty::{Chess, Position, CastlingMode};
+    use shakmaty::fen::{Fen, fen};
+    use shakmaty::uci::Uci;
+
+    use crate::evaluator::PstEvaluator;
+    use crate::score::Score;
+    use crate::bench::read_labeled_fens;
+    use crate::search::{SearchContext, SearchLimits, SearchOptions, SearchStats, Algorithm, iterative_deepening, search, is_draw, negamax_ab, null_move, quiesce, static_eval};
+    use crate::tablebase::Tablebase;
+    use crate::tt::TranspositionTable;
+    use crate::zobrist;
+
+    fn position(fen :&str) -> Chess {
+        fen.parse::<Fen>().unwrap().position(CastlingMode::Standard).unwrap()
+    }
+
+    fn search_fen(fen :&str, algorithm :Algorithm, depth :usize) -> (Score, Vec<String>) {
+        let (score, pv, _) = search_options(fen, algorithm, depth, SearchOptions::default());
+
+        (score, pv)
+    }
+
+    /// Like search_fen with some of the search features switched off, also returns how the search went
+    fn search_options(fen :&str, algorithm :Algorithm, depth :usize, options :SearchOptions) -> (Score, Vec<String>, SearchStats) {
+        let game = position(fen);
+        let tt = TranspositionTable::new(1);
+        let eval = PstEvaluator::default();
+        let mut ctx = SearchContext::new(Arc::new(AtomicBool::new(false)), &tt, &eval, options);
+
+        let limits = SearchLimits { depth: Some(depth), ..SearchLimits::default() };
+        let (score, moves) = iterative_deepening(&mut ctx, algorithm, &game, &limits, |_, _, _, _| ());
+
+        (score, moves.iter().rev().map(|mv| mv.to_string()).collect(), ctx.stats)
+    }
+
+    #[test]
+    fn mate_test() {
+        let fen = "8/8/k7/p7/2K5/1Q6/8/8 w - - 0 1";
+
+        // there's no mate in 2, which a full width search to depth 4 would see
+        let (score, _) = search_fen(fen, Algorithm::Basic, 4);
+
+        assert!(!score.is_mate());
+
+        // searching deeper mustn't settle for a longer mate
+        for algorithm in [Algorithm::AlphaBeta, Algorithm::Pvs] {
+            let (score, pv) = search_fen(fen, algorithm, 8);
+
+            assert_eq!(score, Score::mate_in(5));
+            assert_eq!(score.mate_moves(), Some(3));
+            assert_eq!(pv.len(), 5);
+        }
+
+        // being mated scores the other way around
+        let (score, _) = search_fen("k7/8/2K5/8/8/8/8/1Q6 b - - 0 1", Algorithm::AlphaBeta, 4);
+
+        assert_eq!(score, Score::mated_in(2));
+    }
+
+    #[test]
+    fn stalemate_test() {
+        // no legal moves without being in check is a draw, not a loss
+        for algorithm in Algorithm::ALL {
+            let (score, pv) = search_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", algorithm, 3);
+
+            assert_eq!((score, pv.len()), (Score::DRAW, 0));
+        }
+
+        // with a queen up, white mates instead of stalemating
+        let (score, pv) = search_fen("7k/8/6K1/5Q2/8/8/8/8 w - - 0 1", Algorithm::AlphaBeta, 3);
+
+        assert_eq!(score, Score::mate_in(1));
+        assert_eq!(pv, vec!["Qf5-f8".to_string()]);
+    }
+
+    #[test]
+    fn draw_test() {
+        // a rook and a pawn down, but white has a perpetual check
+        let (score, pv) = search_fen("6k1/6p1/5p2/8/8/1q6/r4PPP/4Q1K1 w - - 0 1", Algorithm::Pvs, 9);
+
+        assert_eq!(score, Score::DRAW);
+        assert_eq!(pv[0], "Qe1-e8");
+
+        // a queen up, but every move is the 100th without a capture or pawn move
+        let (score, _) = search_fen("7k/8/8/8/8/8/8/1Q5K w - - 99 80", Algorithm::AlphaBeta, 4);
+
+        assert_eq!(score, Score::DRAW);
+
+        // a bishop can't mate
+        let (score, _) = search_fen("7k/8/8/8/8/8/8/1B5K w - - 0 1", Algorithm::AlphaBeta, 4);
+
+        assert_eq!(score, Score::DRAW);
+
+        // the knights went out and back before the search started
+        let tt = TranspositionTable::new(0);
+        let eval = PstEvaluator::default();
+        let mut ctx = SearchContext::new(Arc::new(AtomicBool::new(false)), &tt, &eval, SearchOptions::default());
+        let mut game = Chess::default();
+        let mut history = Vec::new();
+
+        for mv in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+            history.push(zobrist::hash(&game));
+            game.play_unchecked(&mv.parse::<Uci>().unwrap().to_move(&game).unwrap());
+        }
+
+        assert!(!is_draw(&ctx, &game, zobrist::hash(&game)));
+
+        ctx.set_history(history);
+
+        assert!(is_draw(&ctx, &game, zobrist::hash(&game)));
+    }
+
+    #[test]
+    fn tablebase_test() {
+        let tablebase = Tablebase::open(concat!(env!("CARGO_MANIFEST_DIR"), "/data/syzygy")).unwrap();
+        let game = position("4k3/8/8/8/8/8/3n4/3QK3 w - - 0 1");
+        let tt = TranspositionTable::new(1);
+        let eval = PstEvaluator::default();
+        let limits = SearchLimits { depth: Some(3), ..SearchLimits::default() };
+
+        // taking the knight leaves a position the tables know is won
+        for algorithm in [Algorithm::AlphaBeta, Algorithm::Pvs] {
+            tt.clear();
+
+            let mut ctx = SearchContext::new(Arc::new(AtomicBool::new(false)), &tt, &eval, SearchOptions::default());
+            ctx.set_tablebase(Some(&tablebase));
+
+            let (score, moves) = iterative_deepening(&mut ctx, algorithm, &game, &limits, |_, _, _, _| ());
+
+            assert_eq!(score, Score::tb_win_in(1));
+            assert_eq!(moves.last().unwrap().to().to_string(), "d2");
+            assert!(ctx.stats.tb_hits > 0);
+        }
+
+        // without the tables it's only a queen up
+        let (score, _) = search_fen("4k3/8/8/8/8/8/3n4/3QK3 w - - 0 1", Algorithm::AlphaBeta, 3);
+
+        assert!(score < Score::tb_win_in(1) && !score.is_mate());
+    }
+
+    #[test]
+    fn quiesce_test() {
+        let tt = TranspositionTable::new(0);
+        let eval = PstEvaluator::default();
+        let mut ctx = SearchContext::new(Arc::new(AtomicBool::new(false)), &tt, &eval, SearchOptions::default());
+
+        // a queen down, but the queen hangs to a pawn at the horizon
+        let game = position("4k3/8/8/3q4/4P3/8/8/4K3 w - - 0 1");
+        let capture = game.legal_moves().into_iter().find(|mv| mv.is_capture()).unwrap();
+        let after = game.clone().play(&capture).unwrap();
+        let score = quiesce(&mut ctx, &game, 0, 0, -Score::INFINITY, Score::INFINITY);
+
+        assert!(static_eval(&ctx, &game) < Score::cp(-500));
+        assert_eq!(score, -static_eval(&ctx, &after));
+
+        // delta pruning doesn't skip a capture that gets back to the window, however high it is
+        assert!(quiesce(&mut ctx, &game, 0, 0, score - 1, score) >= score);
+        assert!(quiesce(&mut ctx, &game, 0, 0, score, score + 1) <= score);
+
+        // but a pawn can't make up for a rook, so with alpha at 0 it isn't even searched
+        let game = position("r3k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1");
+        let stand_pat = static_eval(&ctx, &game);
+
+        ctx.stats = SearchStats::default();
+
+        assert_eq!(quiesce(&mut ctx, &game, 0, 0, Score::DRAW, Score::DRAW + 1), stand_pat);
+        assert_eq!(ctx.stats.qnodes, 1);
+        assert!(quiesce(&mut ctx, &game, 0, 0, -Score::INFINITY, Score::INFINITY) > stand_pat);
+    }
+
+    #[test]
+    fn quiescence_option_test() {
+        // the d5 pawn looks free at depth 1, until the search looks past the horizon and sees the recapture
+        let fen = "4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1";
+        let without = SearchOptions { quiescence: false, ..SearchOptions::default() };
+
+        let (greedy_score, greedy_pv, _) = search_options(fen, Algorithm::AlphaBeta, 1, without);
+        let (score, pv, stats) = search_options(fen, Algorithm::AlphaBeta, 1, SearchOptions::default());
+
+        assert_eq!(greedy_pv[0], "Qd1xd5");
+        assert_ne!(pv[0], "Qd1xd5");
+        assert!(score < greedy_score);
+        assert!(stats.qnodes > 0);
+    }
+
+    #[test]
+    fn null_move_test() {
+        let tt = TranspositionTable::new(0);
+        let eval = PstEvaluator::default();
+        let mut ctx = SearchContext::new(Arc::new(AtomicBool::new(false)), &tt, &eval, SearchOptions::default());
+
+        // whoever moves has to give up their pawn, so passing would be white's best move if it were allowed
+        let game = position("8/8/8/1Kp5/2Pk4/8/8/8 w - - 0 1");
+        let beta = static_eval(&ctx, &game);
+
+        // after a null move black has to give up the c5 pawn, so it looks like white is winning...
+        let mut alpha = -beta;
+        let (value, _) = negamax_ab(&mut ctx, &game.clone().swap_turn().unwrap(), 2, 2, &mut alpha, -(beta - 1));
+
+        assert!(-value >= beta);
+
+        // ...but it's white who has to give up a pawn, which the verification search finds
+        assert_eq!(null_move(&mut ctx, &game, zobrist::hash(&game), 5, 1, beta, false, false, negamax_ab), None);
+        assert_eq!((ctx.stats.null_cutoffs, ctx.stats.null_verify_fails), (0, 1));
+
+        // with a knight to move instead white isn't in zugzwang, so the null move is trusted
+        let game = position("8/8/8/1Kp5/2Pk4/8/8/N7 w - - 0 1");
+        let beta = static_eval(&ctx, &game);
+
+        assert!(null_move(&mut ctx, &game, zobrist::hash(&game), 5, 1, beta, false, false, negamax_ab).is_some());
+        assert_eq!((ctx.stats.null_cutoffs, ctx.stats.null_verify_fails), (1, 1));
+    }
+
+    #[test]
+    fn pvs_test() {
+        // without the pruning that depends on the window, the null windows of PVS only change how many nodes are searched
+        let positions = read_labeled_fens(concat!(env!("CARGO_MANIFEST_DIR"), "/data/rand_gen_10k.fen"));
+        let options = SearchOptions { null_move: false, lmr: false, delta_pruning: false, ..SearchOptions::default() };
+        let eval = PstEvaluator::default();
+        let (ab_tt, pvs_tt) = (TranspositionTable::new(1), TranspositionTable::new(1));
+
+        for (_, game) in &positions[..40] {
+            ab_tt.new_search();
+            pvs_tt.new_search();
+
+            let mut ab = SearchContext::new(Arc::new(AtomicBool::new(false)), &ab_tt, &eval, options.clone());
+            let mut pvs = SearchContext::new(Arc::new(AtomicBool::new(false)), &pvs_tt, &eval, options.clone());
+
+            assert_eq!(search(&mut ab, Algorithm::AlphaBeta, game, 3).0, search(&mut pvs, Algorithm::Pvs, game, 3).0, "{}", fen(game));
+        }
+    }
+
+    #[test]
+    fn lmr_test() {
+        // plenty of quiet moves, some of which do better than the reduced search expects
+        let fen = "r5k1/5ppp/8/8/8/8/1Q3PPP/6K1 w - - 0 1";
+        let reduced = SearchOptions { null_move: false, ..SearchOptions::default() };
+        let full = SearchOptions { null_move: false, lmr: false, ..SearchOptions::default() };
+
+        for algorithm in [Algorithm::AlphaBeta, Algorithm::Pvs] {
+            let (score, _, stats) = search_options(fen, algorithm, 4, reduced.clone());
+            let (full_score, _, full_stats) = search_options(fen, algorithm, 4, full.clone());
+
+            assert!(stats.lmr_reductions > 0 && stats.lmr_researches > 0);
+            assert_eq!(full_stats.lmr_reductions, 0);
+
+            // the moves that beat alpha were searched again at full depth, so nothing was missed here
+            assert_eq!(score, full_score);
+        }
+    }
+
+    #[test]
+    fn aspiration_test() {
+        // a window of a centipawn is bound to miss, and has to be widened until the score fits
+        let exact = SearchOptions { null_move: false, lmr: false, delta_pruning: false, ..SearchOptions::default() };
+        let narrow = SearchOptions { aspiration_window: 1, ..exact.clone() };
+        let full = SearchOptions { aspiration: false, ..exact };
+
+        for fen in ["r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4", "4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1"] {
+            for algorithm in [Algorithm::AlphaBeta, Algorithm::Pvs] {
+                let (score, _, stats) = search_options(fen, algorithm, 4, narrow.clone());
+                let (full_score, _, full_stats) = search_options(fen, algorithm, 4, full.clone());
+
+                assert!(stats.aspiration_fail_lows + stats.aspiration_fail_highs > 0);
+                assert_eq!(full_stats.aspiration_fail_lows + full_stats.aspiration_fail_highs, 0);
+                assert_eq!(score, full_score, "{} {:?}", fen, algorithm);
+            }
+        }
+    }
+
+    #[test]
+    fn pv_test() {
+        // searching again with the table full of the first search's results still gives the whole mating line
+        let game = position("8/8/k7/p7/2K5/1Q6/8/8 w - - 0 1");
+        let tt = TranspositionTable::new(1);
+        let eval = PstEvaluator::default();
+        let limits = SearchLimits { depth: Some(8), ..SearchLimits::default() };
+
+        for algorithm in [Algorithm::AlphaBeta, Algorithm::Pvs] {
+            for _ in 0..2 {
+                tt.new_search();
+
+                let mut ctx = SearchContext::new(Arc::new(AtomicBool::new(false)), &tt, &eval, SearchOptions::default());
+                let (score, moves) = iterative_deepening(&mut ctx, algorithm, &game, &limits, |_, _, _, _| ());
+
+                assert_eq!(score, Score::mate_in(5));
+                assert_eq!(moves.len(), 5, "{:?}", moves.iter().rev().map(|mv| mv.to_string()).collect::<Vec<_>>());
+            }
+        }
+    }
+}
@@ -0,0 +1,813 @@
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use rayon::ThreadPoolBuilder;
+use rayon::prelude::*;
+
+use shakmaty::{Chess, Position, Setup, Color, Move, Outcome, CastlingMode};
+use shakmaty::fen;
+use shakmaty::san::SanPlus;
+use shakmaty::uci::Uci;
+
+use crate::cli::parse_position;
+use crate::eval::BUILTIN_WEIGHTS;
+use crate::evaluator::{Evaluator, EVALUATORS, load_evaluator};
+use crate::score::Score;
+use crate::search::{SearchContext, SearchLimits, SearchOptions, Algorithm, iterative_deepening};
+use crate::time::TimeBudget;
+use crate::tt::TranspositionTable;
+use crate::weights::Weights;
+use crate::zobrist;
+
+const DEFAULT_HASH_MB :usize = 16;
+
+/// PGN lines are kept under this many characters
+const PGN_LINE_LENGTH :usize = 80;
+
+pub const USAGE :&str = "[--a key=value,...] [--b key=value,...] [--openings file] [--games N] [--tc seconds+inc] [--concurrency N] [--pgn file] [--sprt elo0,elo1]";
+
+/// One side of a match: fishermann with some search and evaluation settings
+pub struct Engine {
+    pub name: String,
+    pub algorithm: Algorithm,
+    pub options: SearchOptions,
+    pub evaluator: Arc<dyn Evaluator>,
+    pub hash_mb: usize,
+}
+
+impl Engine {
+    /// Parses settings like "name=tuned,eval=tuned.weights,lmr=false", anything not given keeps the engine's default
+    ///
+    /// The keys are name, algorithm, eval, evaluator, evaluator_file, hash, and the switches of SearchOptions
+    pub fn parse(default_name :&str, spec :&str) -> Result<Engine, String> {
+        let mut name = default_name.to_string();
+        let mut algorithm = Algorithm::default();
+        let mut options = SearchOptions::default();
+        let mut hash_mb = DEFAULT_HASH_MB;
+        let mut weights = BUILTIN_WEIGHTS;
+        let mut evaluator = EVALUATORS[0].to_string();
+        let mut evaluator_file = None;
+
+        for setting in spec.split(',').filter(|s| !s.is_empty()) {
+            let (key, value) = setting.split_once('=').ok_or(format!("Expected key=value, found: {}", setting))?;
+            let flag = || value.parse::<bool>().map_err(|_| format!("Expected true or false for {}: {}", key, value));
+            let number = || value.parse::<usize>().map_err(|_| format!("Invalid number for {}: {}", key, value));
+
+            match key {
+                "name" => name = value.to_string(),
+                "algorithm" => algorithm = Algorithm::from_name(value).ok_or(format!("Unknown algorithm: {}", value))?,
+                "eval" => weights = Weights::load(value, &BUILTIN_WEIGHTS)?,
+                "evaluator" => evaluator = value.to_string(),
+                "evaluator_file" => evaluator_file = Some(value.to_string()),
+                "hash" => hash_mb = number()?,
+                "quiescence" => options.quiescence = flag()?,
+                "qs_checks" => options.qs_checks = flag()?,
+                "delta_pruning" => options.delta_pruning = flag()?,
+                "see" => options.see = flag()?,
+                "null_move" => options.null_move = flag()?,
+                "null_move_reduction" => options.null_move_reduction = number()?,
+                "lmr" => options.lmr = flag()?,
+                "lmr_full_moves" => options.lmr_full_moves = number()?,
+                "lmr_min_depth" => options.lmr_min_depth = number()?,
+                "lmr_reduction" => options.lmr_reduction = number()?,
+                "aspiration" => options.aspiration = flag()?,
+                "aspiration_window" => options.aspiration_window = number()? as i32,
+                _ => return Err(format!("Unknown setting: {}", key))
+            }
+        }
+
+        let evaluator = load_evaluator(&evaluator, evaluator_file.as_deref(), &weights)?;
+
+        Ok(Engine { name, algorithm, options, evaluator, hash_mb })
+    }
+
+    /// A player for one game, with its own transposition table
+    pub fn player(&self) -> EnginePlayer<'_> {
+        EnginePlayer { engine: self, tt: TranspositionTable::new(self.hash_mb) }
+    }
+}
+
+/// Anything that can play a side of a game: fishermann itself, or an engine we talk UCI to
+pub trait Player {
+    fn name(&self) -> &str;
+
+    /// The move to play in game, which is start after moves, and the score of the position for the side to move if known
+    ///
+    /// history holds the hashes of the positions before this one, clocks the time left for white and black.
+    /// An error forfeits the game.
+    fn play(&mut self, game :&Chess, start :&Chess, moves :&[Move], history :&[u64], clocks :[Duration; 2], increment :Duration) -> Result<(Move, Option<Score>), String>;
+}
+
+/// fishermann playing a game in this process
+pub struct EnginePlayer<'a> {
+    engine: &'a Engine,
+    tt: TranspositionTable,
+}
+
+impl Player for EnginePlayer<'_> {
+    fn name(&self) -> &str {
+        &self.engine.name
+    }
+
+    /// Searches for a move with the time left on our clock
+    fn play(&mut self, game :&Chess, _start :&Chess, _moves :&[Move], history :&[u64], clocks :[Duration; 2], increment :Duration) -> Result<(Move, Option<Score>), String> {
+        let clock = clocks[if game.turn() == Color::White { 0 } else { 1 }];
+        let limits = SearchLimits {
+            time: Some(TimeBudget::Clock { remaining: clock, increment, moves_to_go: None }),
+            ..SearchLimits::default()
+        };
+
+        self.tt.new_search();
+
+        let mut ctx = SearchContext::new(Arc::new(AtomicBool::new(false)), &self.tt, &self.engine.evaluator, self.engine.options.clone());
+        ctx.set_history(history.to_vec());
+
+        let (score, moves) = iterative_deepening(&mut ctx, self.engine.algorithm, game, &limits, |_, _, _, _| ());
+
+        // if not even depth 1 finished, any legal move is better than losing on time
+        match moves.last() {
+            Some(mv) => Ok((mv.clone(), Some(score))),
+            None => game.legal_moves().first().cloned().map(|mv| (mv, None)).ok_or("No legal move".to_string())
+        }
+    }
+}
+
+/// The time each side starts with, and gets after each of its moves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeControl {
+    pub base: Duration,
+    pub increment: Duration,
+}
+
+impl TimeControl {
+    /// Parses seconds plus increment in seconds, like 10+0.1
+    pub fn parse(text :&str) -> Result<TimeControl, String> {
+        let (base, increment) = text.split_once('+').unwrap_or((text, "0"));
+        let seconds = |s :&str| s.parse::<f64>().ok().filter(|s| *s >= 0.0).map(Duration::from_secs_f64).ok_or(format!("Invalid time control: {}", text));
+
+        Ok(TimeControl { base: seconds(base)?, increment: seconds(increment)? })
+    }
+}
+
+/// The PGN way of writing a time control, in seconds
+impl std::fmt::Display for TimeControl {
+    fn fmt(&self, f :&mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}+{}", self.base.as_secs_f64(), self.increment.as_secs_f64())
+    }
+}
+
+/// A finished game, with everything needed to write it out
+#[derive(Debug, Clone)]
+pub struct Game {
+    pub white: String,
+    pub black: String,
+    pub start: Chess,
+    pub moves: Vec<Move>,
+    pub outcome: Outcome,
+    /// Why the game ended, in words
+    pub reason: String,
+    /// The PGN Termination: normal, time forfeit, rules infraction or adjudication
+    pub termination: &'static str,
+}
+
+impl Game {
+    /// The game in PGN, round is the number of the game in the match
+    pub fn to_pgn(&self, round :usize, time_control :&TimeControl) -> String {
+        let mut pgn = String::new();
+        let result = result_string(self.outcome);
+
+        writeln!(pgn, "[Event \"fishermann match\"]").unwrap();
+        writeln!(pgn, "[Site \"?\"]").unwrap();
+        writeln!(pgn, "[Date \"????.??.??\"]").unwrap();
+        writeln!(pgn, "[Round \"{}\"]", round).unwrap();
+        writeln!(pgn, "[White \"{}\"]", self.white).unwrap();
+        writeln!(pgn, "[Black \"{}\"]", self.black).unwrap();
+        writeln!(pgn, "[Result \"{}\"]", result).unwrap();
+
+        let start = fen::fen(&self.start);
+
+        if start != fen::fen(&Chess::default()) {
+            writeln!(pgn, "[FEN \"{}\"]", start).unwrap();
+            writeln!(pgn, "[SetUp \"1\"]").unwrap();
+        }
+
+        writeln!(pgn, "[TimeControl \"{}\"]", time_control).unwrap();
+        writeln!(pgn, "[Termination \"{}\"]", self.termination).unwrap();
+        writeln!(pgn).unwrap();
+
+        // the movetext is split into tokens, then wrapped
+        let mut tokens = Vec::new();
+        let mut game = self.start.clone();
+
+        for (i, mv) in self.moves.iter().enumerate() {
+            if game.turn() == Color::White {
+                tokens.push(format!("{}.", game.fullmoves()));
+            } else if i == 0 {
+                tokens.push(format!("{}...", game.fullmoves()));
+            }
+
+            tokens.push(SanPlus::from_move_and_play_unchecked(&mut game, mv).to_string());
+        }
+
+        tokens.push(format!("{{{}}}", self.reason));
+        tokens.push(result.to_string());
+
+        let mut line = String::new();
+
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > PGN_LINE_LENGTH {
+                writeln!(pgn, "{}", line).unwrap();
+                line.clear();
+            }
+
+            if !line.is_empty() {
+                line.push(' ');
+            }
+
+            line.push_str(&token);
+        }
+
+        writeln!(pgn, "{}\n", line).unwrap();
+
+        pgn
+    }
+}
+
+pub fn result_string(outcome :Outcome) -> &'static str {
+    match outcome {
+        Outcome::Decisive { winner: Color::White } => "1-0",
+        Outcome::Decisive { winner: Color::Black } => "0-1",
+        Outcome::Draw => "1/2-1/2"
+    }
+}
+
+/// Why the game is over, if it is: mate, stalemate, not enough material to mate, threefold repetition, or the 50 move rule
+/// history holds the hashes of the positions before this one
+pub fn game_over(game :&Chess, history :&[u64]) -> Option<(Outcome, &'static str)> {
+    if game.is_checkmate() {
+        return Some((Outcome::Decisive { winner: !game.turn() }, "checkmate"));
+    }
+
+    if game.is_stalemate() {
+        return Some((Outcome::Draw, "stalemate"));
+    }
+
+    if game.is_insufficient_material() {
+        return Some((Outcome::Draw, "insufficient material"));
+    }
+
+    if game.halfmoves() >= 100 {
+        return Some((Outcome::Draw, "50 move rule"));
+    }
+
+    let hash = zobrist::hash(game);
+
+    if history.iter().filter(|h| **h == hash).count() >= 2 {
+        return Some((Outcome::Draw, "threefold repetition"));
+    }
+
+    None
+}
+
+/// Ends games early on the scores the engines report, the way a tournament director would
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Adjudication {
+    /// A side loses when both engines agree it is this many centipawns behind...
+    pub resign_score: i32,
+    /// ...for this many moves each
+    pub resign_moves: usize,
+    /// A draw when both engines score the game within this many centipawns of equal...
+    pub draw_score: i32,
+    /// ...for this many moves each...
+    pub draw_moves: usize,
+    /// ...once this move number is reached
+    pub draw_after: u32,
+    /// A draw once the game is this many moves long, so no game runs forever
+    pub max_moves: usize,
+}
+
+impl Default for Adjudication {
+    fn default() -> Self {
+        Adjudication { resign_score: 1000, resign_moves: 3, draw_score: 10, draw_moves: 8, draw_after: 40, max_moves: 200 }
+    }
+}
+
+impl Adjudication {
+    /// scores are from white's point of view, one for each move played so far, fullmoves is the current move number
+    pub fn adjudicate(&self, scores :&[Option<Score>], fullmoves :u32) -> Option<(Outcome, &'static str)> {
+        // the last n scores, when every move reported one
+        let last = |n :usize| if n > 0 && scores.len() >= n {
+            scores[scores.len() - n..].iter().cloned().collect::<Option<Vec<_>>>()
+        } else {
+            None
+        };
+
+        if let Some(last) = last(self.resign_moves.saturating_mul(2)) {
+            if last.iter().all(|s| s.raw() >= self.resign_score) {
+                return Some((Outcome::Decisive { winner: Color::White }, "black resigns"));
+            }
+
+            if last.iter().all(|s| s.raw() <= self.resign_score.saturating_neg()) {
+                return Some((Outcome::Decisive { winner: Color::Black }, "white resigns"));
+            }
+        }
+
+        if fullmoves >= self.draw_after && last(self.draw_moves.saturating_mul(2)).is_some_and(|last| last.iter().all(|s| s.raw().abs() <= self.draw_score)) {
+            return Some((Outcome::Draw, "draw by adjudication"));
+        }
+
+        if scores.len() >= self.max_moves.saturating_mul(2) {
+            return Some((Outcome::Draw, "move limit"));
+        }
+
+        None
+    }
+}
+
+/// Plays a game from start, each player gets its own clock
+///
+/// A player that runs out of time, or fails to give a legal move, loses. Without an adjudication games are played to the end.
+pub fn play_game<'a>(white :&'a mut dyn Player, black :&'a mut dyn Player, start :&Chess, time_control :&TimeControl, adjudication :Option<&Adjudication>) -> Game {
+    let players = [white, black];
+    let mut clocks = [time_control.base; 2];
+
+    let mut game = start.clone();
+    let mut history = Vec::new();
+    let mut moves = Vec::new();
+    let mut scores = Vec::new();
+
+    let (outcome, reason, termination) = loop {
+        if let Some((outcome, reason)) = game_over(&game, &history) {
+            break (outcome, reason.to_string(), "normal");
+        }
+
+        if let Some((outcome, reason)) = adjudication.and_then(|a| a.adjudicate(&scores, game.fullmoves().get())) {
+            break (outcome, reason.to_string(), "adjudication");
+        }
+
+        let side = if game.turn() == Color::White { 0 } else { 1 };
+        let move_start = Instant::now();
+        let result = players[side].play(&game, start, &moves, &history, clocks, time_control.increment);
+        let elapsed = move_start.elapsed();
+        let name = players[side].name().to_string();
+
+        if elapsed > clocks[side] {
+            // a flag only loses when the opponent could still mate
+            if game.has_insufficient_material(!game.turn()) {
+                break (Outcome::Draw, format!("{} ran out of time, without mating material against it", name), "time forfeit");
+            }
+
+            break (Outcome::Decisive { winner: !game.turn() }, format!("{} loses on time", name), "time forfeit");
+        }
+
+        let (mv, score) = match result {
+            Ok(result) => result,
+            Err(e) => break (Outcome::Decisive { winner: !game.turn() }, format!("{} forfeits: {}", name, e), "rules infraction")
+        };
+
+        if !game.is_legal(&mv) {
+            break (Outcome::Decisive { winner: !game.turn() }, format!("{} forfeits: illegal move {}", name, mv.to_uci(CastlingMode::Standard)), "rules infraction");
+        }
+
+        clocks[side] = clocks[side] - elapsed + time_control.increment;
+
+        // the scores are kept from white's point of view, so both engines' scores can be compared
+        scores.push(score.map(|s| if side == 0 { s } else { -s }));
+        history.push(zobrist::hash(&game));
+        game.play_unchecked(&mv);
+        moves.push(mv);
+    };
+
+    Game {
+        white: players[0].name().to_string(),
+        black: players[1].name().to_string(),
+        start: start.clone(),
+        moves,
+        outcome,
+        reason,
+        termination
+    }
+}
+
+/// Wins, draws and losses of the first engine, and what they say about the difference in strength
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MatchScore {
+    pub wins: u64,
+    pub draws: u64,
+    pub losses: u64,
+}
+
+impl MatchScore {
+    pub fn games(&self) -> u64 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The share of the points won, between 0 and 1
+    fn points(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    /// The variance of a single game's result around the mean
+    fn variance(&self) -> f64 {
+        let points = self.points();
+        let games = self.games() as f64;
+
+        (self.wins as f64 * (1.0 - points).powi(2) + self.draws as f64 * (0.5 - points).powi(2) + self.losses as f64 * points.powi(2)) / games
+    }
+
+    /// The Elo difference and its 95% error margin
+    pub fn elo(&self) -> (f64, f64) {
+        if self.games() == 0 {
+            return (0.0, 0.0);
+        }
+
+        let points = self.points();
+        let margin = 1.96 * (self.variance() / self.games() as f64).sqrt();
+
+        (elo(points), (elo(points + margin) - elo(points - margin)) / 2.0)
+    }
+
+    /// The log likelihood ratio of elo1 over elo0, with the normal approximation of the trinomial model
+    pub fn llr(&self, elo0 :f64, elo1 :f64) -> f64 {
+        let variance = if self.games() == 0 { 0.0 } else { self.variance() };
+
+        if variance == 0.0 {
+            return 0.0;
+        }
+
+        let (score0, score1) = (expected_score(elo0), expected_score(elo1));
+
+        (score1 - score0) * (2.0 * self.points() - score0 - score1) / (2.0 * variance / self.games() as f64)
+    }
+}
+
+/// The expected share of the points for an Elo difference
+fn expected_score(elo :f64) -> f64 {
+    1.0 / (1.0 + 10_f64.powf(-elo / 400.0))
+}
+
+/// The inverse of expected_score, kept finite when one side scores everything
+fn elo(points :f64) -> f64 {
+    let points = points.clamp(1e-6, 1.0 - 1e-6);
+
+    400.0 * (points / (1.0 - points)).log10()
+}
+
+/// A sequential probability ratio test: is the first engine elo1 stronger (H1), or only elo0 (H0)?
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    /// The chance of accepting H1 when H0 is true
+    pub alpha: f64,
+    /// The chance of accepting H0 when H1 is true
+    pub beta: f64,
+}
+
+impl Sprt {
+    /// Parses elo0,elo1 with alpha and beta of 0.05
+    pub fn parse(text :&str) -> Result<Sprt, String> {
+        let (elo0, elo1) = text.split_once(',').ok_or(format!("Expected elo0,elo1: {}", text))?;
+        let elo = |e :&str| e.trim().parse::<f64>().map_err(|_| format!("Invalid Elo: {}", e));
+
+        Ok(Sprt { elo0: elo(elo0)?, elo1: elo(elo1)?, alpha: 0.05, beta: 0.05 })
+    }
+
+    /// The LLR below which H0 is accepted, and above which H1 is
+    pub fn bounds(&self) -> (f64, f64) {
+        ((self.beta / (1.0 - self.alpha)).ln(), ((1.0 - self.beta) / self.alpha).ln())
+    }
+
+    pub fn verdict(&self, score :&MatchScore) -> Option<&'static str> {
+        let llr = score.llr(self.elo0, self.elo1);
+        let (lower, upper) = self.bounds();
+
+        if llr >= upper {
+            Some("H1 accepted")
+        } else if llr <= lower {
+            Some("H0 accepted")
+        } else {
+            None
+        }
+    }
+}
+
+/// Reads the openings, a FEN or EPD per line, or a line of UCI moves from the start position
+pub fn read_openings(path :&str) -> Result<Vec<Chess>, String> {
+    let file = BufReader::new(File::open(path).map_err(|e| format!("Error opening {}: {}", path, e))?);
+    let mut openings = Vec::new();
+
+    for line in file.lines() {
+        let line = line.map_err(|e| format!("Error reading {}: {}", path, e))?;
+
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let opening = parse_position(&line).or_else(|e| {
+            line.split_whitespace().try_fold(Chess::default(), |game, mv| {
+                let mv = mv.parse::<Uci>().ok().and_then(|uci| uci.to_move(&game).ok()).ok_or(e.clone())?;
+
+                game.play(&mv).map_err(|_| e.clone())
+            })
+        })?;
+
+        openings.push(opening);
+    }
+
+    if openings.is_empty() {
+        return Err(format!("No openings in {}", path));
+    }
+
+    Ok(openings)
+}
+
+/// The opening of the nth game, each one is played twice so both engines get each side, and the start position when there are none
+pub fn opening(openings :&[Chess], n :usize) -> Chess {
+    if openings.is_empty() {
+        Chess::default()
+    } else {
+        openings[(n / 2) % openings.len()].clone()
+    }
+}
+
+/// The options of a match from the command line, the engines are settings for Engine::parse
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchOptions {
+    pub a: String,
+    pub b: String,
+    /// Without a file of openings every game starts from the start position
+    pub openings: Option<String>,
+    pub games: usize,
+    pub time_control: TimeControl,
+    pub concurrency: usize,
+    pub pgn: Option<String>,
+    pub sprt: Option<Sprt>,
+}
+
+impl Default for MatchOptions {
+    fn default() -> Self {
+        MatchOptions {
+            a: String::new(),
+            b: String::new(),
+            openings: None,
+            games: 100,
+            time_control: TimeControl { base: Duration::from_secs(10), increment: Duration::from_millis(100) },
+            concurrency: 1,
+            pgn: None,
+            sprt: None
+        }
+    }
+}
+
+impl MatchOptions {
+    /// Parses the arguments after the subcommand
+    pub fn parse(args :&[String]) -> Result<MatchOptions, String> {
+        let mut options = MatchOptions::default();
+        let mut args = args.iter();
+
+        while let Some(flag) = args.next() {
+            let value = args.next().map(|v| v.to_string()).ok_or(format!("Missing value for {}", flag))?;
+            let number = |v :&str| v.parse::<usize>().map_err(|_| format!("Invalid number for {}: {}", flag, v));
+
+            match flag.as_str() {
+                "--a" => options.a = value,
+                "--b" => options.b = value,
+                "--openings" => options.openings = Some(value),
+                "--games" => options.games = number(&value)?,
+                "--tc" => options.time_control = TimeControl::parse(&value)?,
+                "--concurrency" => options.concurrency = number(&value)?.max(1),
+                "--pgn" => options.pgn = Some(value),
+                "--sprt" => options.sprt = Some(Sprt::parse(&value)?),
+                _ => return Err(format!("Unknown option: {}", flag))
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// Plays games between a and b, each opening twice with the colours swapped, running concurrency games at once
+///
+/// The games are written to the PGN file as they finish, and the score so far is printed after each one.
+/// With an SPRT the match stops early once it reaches a verdict.
+pub fn run(options :&MatchOptions) -> Result<(), String> {
+    let (a, b) = (Engine::parse("a", &options.a)?, Engine::parse("b", &options.b)?);
+    let openings = match &options.openings {
+        Some(path) => read_openings(path)?,
+        None => vec![Chess::default()]
+    };
+    let (games, time_control, concurrency, sprt) = (options.games, &options.time_control, options.concurrency, options.sprt);
+    let pool = ThreadPoolBuilder::new().num_threads(concurrency).build().map_err(|e| format!("Error creating thread pool: {}", e))?;
+    let pgn = options.pgn.as_ref().map(|path| File::create(path).map_err(|e| format!("Error creating {}: {}", path, e))).transpose()?;
+    let state = Mutex::new((MatchScore::default(), pgn));
+    let stop = AtomicBool::new(false);
+
+    println!("{} vs {}, {} games at {}, {} at a time", a.name, b.name, games, time_control, concurrency);
+
+    pool.install(|| {
+        (0..games).into_par_iter().for_each(|i| {
+            if stop.load(Ordering::Relaxed) {
+                return;
+            }
+
+            let opening = opening(&openings, i);
+            let a_white = i % 2 == 0;
+            let (white, black) = if a_white { (&a, &b) } else { (&b, &a) };
+
+            let game = play_game(&mut white.player(), &mut black.player(), &opening, time_control, None);
+
+            let mut state = state.lock().unwrap();
+            let (score, pgn) = &mut *state;
+
+            match game.outcome.winner() {
+                None => score.draws += 1,
+                Some(winner) if (winner == Color::White) == a_white => score.wins += 1,
+                Some(_) => score.losses += 1
+            }
+
+            if let Some(pgn) = pgn {
+                pgn.write_all(game.to_pgn(i + 1, time_control).as_bytes()).expect("Error writing PGN file");
+            }
+
+            let (elo, margin) = score.elo();
+            let mut report = format!("Game {} {} vs {}: {} {}, {}: +{} ={} -{}, Elo {:.1} +/- {:.1}",
+                                     i + 1, game.white, game.black, result_string(game.outcome), game.reason,
+                                     a.name, score.wins, score.draws, score.losses, elo, margin);
+
+            if let Some(sprt) = sprt {
+                let (lower, upper) = sprt.bounds();
+
+                write!(report, ", LLR {:.2} ({:.2}, {:.2})", score.llr(sprt.elo0, sprt.elo1), lower, upper).unwrap();
+
+                if let Some(verdict) = sprt.verdict(score) {
+                    write!(report, " {}", verdict).unwrap();
+                    stop.store(true, Ordering::Relaxed);
+                }
+            }
+
+            println!("{}", report);
+        });
+    });
+
+    let (score, _) = state.into_inner().unwrap();
+    let (elo, margin) = score.elo();
+
+    println!();
+    println!("{} vs {}: +{} ={} -{} in {} games, Elo difference {:.1} +/- {:.1}", a.name, b.name, score.wins, score.draws, score.losses, score.games(), elo, margin);
+
+    if let Some(sprt) = sprt {
+        println!("SPRT elo0 {} elo1 {}: LLR {:.2}, {}", sprt.elo0, sprt.elo1, score.llr(sprt.elo0, sprt.elo1), sprt.verdict(&score).unwrap_or("no verdict"));
+    }
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod selfplay_tests {
+    use std::{env, fs, process};
+    use std::time::Duration;
+
+    use shakmaty::{Chess, Position, Color, Outcome};
+    use shakmaty::fen;
+    use shakmaty::uci::Uci;
+
+    use crate::cli::parse_position;
+    use crate::score::Score;
+    use crate::selfplay::{Adjudication, Engine, MatchOptions, MatchScore, Sprt, TimeControl, game_over, opening, play_game, read_openings, run};
+    use crate::zobrist;
+
+    #[test]
+    fn game_test() {
+        let engine = Engine::parse("a", "algorithm=ab,lmr=false").unwrap();
+        let tc = TimeControl::parse("1+0.01").unwrap();
+
+        assert_eq!(tc, TimeControl { base: Duration::from_secs(1), increment: Duration::from_millis(10) });
+        assert!(Engine::parse("a", "lmr=maybe").is_err());
+        assert!(Engine::parse("a", "speed=11").is_err());
+
+        // white mates at once
+        let start = parse_position("7k/8/6K1/5Q2/8/8/8/8 w - - 0 1").unwrap();
+        let game = play_game(&mut engine.player(), &mut engine.player(), &start, &tc, None);
+
+        assert_eq!(game.outcome, Outcome::Decisive { winner: Color::White });
+        assert_eq!((game.moves.len(), game.reason.as_str()), (1, "checkmate"));
+
+        let pgn = game.to_pgn(3, &tc);
+
+        assert!(pgn.contains("[Round \"3\"]\n[White \"a\"]\n[Black \"a\"]\n[Result \"1-0\"]\n[FEN \"7k/8/6K1/5Q2/8/8/8/8 w - - 0 1\"]"));
+        assert!(pgn.contains("[TimeControl \"1+0.01\"]"));
+        assert!(pgn.ends_with("1. Qf8# {checkmate} 1-0\n\n") || pgn.ends_with("1. Qc8# {checkmate} 1-0\n\n"));
+
+        // the knights go out and back twice
+        let mut game = Chess::default();
+        let mut history = Vec::new();
+
+        for mv in ["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8"] {
+            assert!(game_over(&game, &history).is_none());
+
+            history.push(zobrist::hash(&game));
+            let mv = mv.parse::<Uci>().unwrap().to_move(&game).unwrap();
+            game.play_unchecked(&mv);
+        }
+
+        assert_eq!(game_over(&game, &history), Some((Outcome::Draw, "threefold repetition")));
+        assert_eq!(game_over(&parse_position("8/8/8/8/8/8/8/K6k w - - 0 1").unwrap(), &[]), Some((Outcome::Draw, "insufficient material")));
+        assert_eq!(game_over(&parse_position("7k/8/8/8/8/8/8/KR6 w - - 100 80").unwrap(), &[]), Some((Outcome::Draw, "50 move rule")));
+    }
+
+    #[test]
+    fn openings_test() {
+        let path = env::temp_dir().join(format!("fishermann_openings_{}.txt", process::id()));
+        let path = path.to_str().unwrap();
+
+        fs::write(path, "# nothing but comments\n\n").unwrap();
+        assert!(read_openings(path).is_err());
+
+        fs::write(path, "e2e4 e7e5\n7k/8/6K1/5Q2/8/8/8/8 w - - 0 1\n").unwrap();
+        let openings = read_openings(path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(openings.len(), 2);
+        assert_eq!(fen::fen(&opening(&openings, 1)), "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2");
+        assert_eq!(fen::fen(&opening(&openings, 2)), "7k/8/6K1/5Q2/8/8/8/8 w - - 0 1");
+        assert_eq!(fen::fen(&opening(&openings, 4)), fen::fen(&openings[0]));
+
+        // without openings every game starts from the start position
+        assert_eq!(fen::fen(&opening(&[], 5)), fen::fen(&Chess::default()));
+    }
+
+    #[test]
+    fn match_options_test() {
+        let args = |line :&str| line.split_whitespace().map(|a| a.to_string()).collect::<Vec<_>>();
+        let options = MatchOptions::parse(&args("--a name=new,lmr=false --games 10 --tc 5+0.05 --sprt 0,5")).unwrap();
+
+        assert_eq!((options.a.as_str(), options.b.as_str(), options.games), ("name=new,lmr=false", "", 10));
+        assert_eq!(options.time_control, TimeControl::parse("5+0.05").unwrap());
+        assert_eq!(options.sprt.map(|sprt| sprt.elo1), Some(5.0));
+
+        // bad arguments are errors, not panics
+        assert_eq!(MatchOptions::parse(&args("--games")).unwrap_err(), "Missing value for --games");
+        assert_eq!(MatchOptions::parse(&args("--games many")).unwrap_err(), "Invalid number for --games: many");
+        assert_eq!(MatchOptions::parse(&args("--rounds 2")).unwrap_err(), "Unknown option: --rounds");
+        assert!(MatchOptions::parse(&args("--tc fast")).is_err());
+        assert!(run(&MatchOptions::parse(&args("--a speed=11")).unwrap()).is_err());
+        assert!(run(&MatchOptions::parse(&args("--openings /nonexistent/openings.epd")).unwrap()).is_err());
+    }
+
+    #[test]
+    fn adjudication_test() {
+        let adjudication = Adjudication { resign_score: 500, resign_moves: 2, draw_score: 10, draw_moves: 2, draw_after: 30, max_moves: 100 };
+        let scores = |raw :&[i32]| raw.iter().map(|s| Some(Score::cp(*s as i64))).collect::<Vec<_>>();
+
+        assert_eq!(adjudication.adjudicate(&scores(&[600, 700, 800]), 10), None);
+        assert_eq!(adjudication.adjudicate(&scores(&[600, 700, 800, 900]), 10), Some((Outcome::Decisive { winner: Color::White }, "black resigns")));
+        assert_eq!(adjudication.adjudicate(&scores(&[-600, -700, -800, -900]), 10), Some((Outcome::Decisive { winner: Color::Black }, "white resigns")));
+        assert_eq!(adjudication.adjudicate(&scores(&[600, 700, 800, 100]), 10), None);
+
+        // a mate is always enough to resign
+        let mut mate = scores(&[600, 700, 800]);
+        mate.push(Some(Score::mate_in(3)));
+        assert_eq!(adjudication.adjudicate(&mate, 10).map(|(outcome, _)| outcome), Some(Outcome::Decisive { winner: Color::White }));
+
+        // every move needs a score
+        let mut missing = scores(&[600, 700, 800]);
+        missing.insert(1, None);
+        assert_eq!(adjudication.adjudicate(&missing, 10), None);
+
+        assert_eq!(adjudication.adjudicate(&scores(&[0, 5, -5, 0]), 29), None);
+        assert_eq!(adjudication.adjudicate(&scores(&[0, 5, -5, 0]), 30), Some((Outcome::Draw, "draw by adjudication")));
+        assert_eq!(adjudication.adjudicate(&vec![None; 200], 101), Some((Outcome::Draw, "move limit")));
+
+        // counts too big to double never adjudicate, instead of overflowing
+        let never = Adjudication { resign_score: i32::MIN, resign_moves: usize::MAX, draw_score: 10, draw_moves: usize::MAX, draw_after: 0, max_moves: usize::MAX };
+
+        assert_eq!(never.adjudicate(&scores(&[0, 0, 0, 0]), 60), None);
+    }
+
+    #[test]
+    fn sprt_test() {
+        // 60% of the points is about 70 Elo
+        let score = MatchScore { wins: 50, draws: 20, losses: 30 };
+        let (elo, margin) = score.elo();
+
+        assert!((elo - 70.4).abs() < 0.1);
+        assert!(margin > 50.0 && margin < 80.0);
+        assert_eq!(MatchScore { wins: 10, draws: 0, losses: 10 }.elo().0, 0.0);
+
+        let sprt = Sprt::parse("0,10").unwrap();
+        let (lower, upper) = sprt.bounds();
+
+        assert!((lower + 2.944).abs() < 0.001 && (upper - 2.944).abs() < 0.001);
+        // 100 games at 60% isn't enough to tell 0 from 10 Elo, 1000 games is
+        assert_eq!(sprt.verdict(&score), None);
+        assert_eq!(sprt.verdict(&MatchScore { wins: 500, draws: 200, losses: 300 }), Some("H1 accepted"));
+        assert_eq!(sprt.verdict(&MatchScore { wins: 300, draws: 200, losses: 500 }), Some("H0 accepted"));
+        assert_eq!(MatchScore::default().llr(0.0, 10.0), 0.0);
+    }
+}
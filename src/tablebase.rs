@@ -0,0 +1,168 @@
+use std::fs;
+
+use shakmaty::{Chess, Position, Setup, Move};
+use shakmaty_syzygy::{Tablebase as Tables, Wdl};
+
+use crate::score::Score;
+
+/// The directories of a SyzygyPath are separated like those of PATH
+#[cfg(windows)]
+const PATH_SEPARATOR :char = ';';
+#[cfg(not(windows))]
+const PATH_SEPARATOR :char = ':';
+
+/// Syzygy endgame tablebases: win/draw/loss tables to probe during the search,
+/// and distance to zeroing (DTZ) tables to pick a move that keeps the result at the root
+pub struct Tablebase {
+    tables: Tables<Chess>,
+    /// The most pieces of any table we have, positions with more are never probed
+    max_pieces: usize,
+}
+
+impl Tablebase {
+    /// Finds the .rtbw and .rtbz files in each directory of a SyzygyPath, the tables themselves are only read when probed
+    pub fn open(path :&str) -> Result<Tablebase, String> {
+        let mut tables = Tables::new();
+        let mut max_pieces = 0;
+
+        for dir in path.split(PATH_SEPARATOR).filter(|d| !d.is_empty()) {
+            let entries = fs::read_dir(dir).map_err(|e| format!("Error reading tablebase directory {}: {}", dir, e))?;
+
+            for entry in entries {
+                let path = entry.map_err(|e| format!("Error reading tablebase directory {}: {}", dir, e))?.path();
+
+                // anything that isn't named like a table is skipped
+                if tables.add_file(&path).is_err() {
+                    continue
+                }
+
+                // KQvK.rtbw has 3 pieces
+                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                    max_pieces = max_pieces.max(stem.chars().filter(|c| *c != 'v').count());
+                }
+            }
+        }
+
+        if max_pieces == 0 {
+            return Err(format!("No Syzygy tables found in {}", path));
+        }
+
+        Ok(Tablebase { tables, max_pieces })
+    }
+
+    /// True if the position has few enough pieces to be in the tables, and no castling rights as the tables don't have those
+    pub fn covers(&self, game :&Chess) -> bool {
+        game.board().occupied().count() <= self.max_pieces && !game.castles().any()
+    }
+
+    /// The result for the side to move with perfect play, ply plies from the root. None if we don't have the table.
+    ///
+    /// The tables assume the fifty-move counter was just reset, so wins and losses the rule turns into draws score 0.
+    pub fn probe_wdl(&self, game :&Chess, ply :usize) -> Option<Score> {
+        if !self.covers(game) {
+            return None;
+        }
+
+        match self.tables.probe_wdl(game).ok()? {
+            Wdl::Win => Some(Score::tb_win_in(ply)),
+            Wdl::Loss => Some(Score::tb_loss_in(ply)),
+            Wdl::CursedWin | Wdl::Draw | Wdl::BlessedLoss => Some(Score::DRAW)
+        }
+    }
+
+    /// The move that keeps the best result and resets the fifty-move counter soonest, with the score of the position.
+    /// None if we don't have the tables, or there are no legal moves.
+    ///
+    /// Unlike probe_wdl this knows about the fifty-move counter: a win that can't zero it in time is a draw.
+    pub fn best_move(&self, game :&Chess) -> Option<(Move, Score)> {
+        if !self.covers(game) {
+            return None;
+        }
+
+        let dtz = self.tables.probe_dtz(game).ok()?.0;
+        let (mv, _) = self.tables.best_move(game).ok()??;
+        let halfmoves = game.halfmoves() as i32;
+
+        let score = if dtz > 0 && dtz + halfmoves <= 100 {
+            Score::tb_win_in(0)
+        } else if dtz < 0 && -dtz + halfmoves <= 100 {
+            Score::tb_loss_in(0)
+        } else {
+            Score::DRAW
+        };
+
+        Some((mv, score))
+    }
+}
+
+
+#[cfg(test)]
+mod tablebase_tests {
+    use shakmaty::{Chess, Position, CastlingMode};
+    use shakmaty::fen::Fen;
+
+    use crate::score::Score;
+    use crate::tablebase::Tablebase;
+
+    /// 3 piece tables made by utils/syzygy_gen
+    const FIXTURES :&str = concat!(env!("CARGO_MANIFEST_DIR"), "/data/syzygy");
+
+    fn position(fen :&str) -> Chess {
+        fen.parse::<Fen>().unwrap().position(CastlingMode::Standard).unwrap()
+    }
+
+    #[test]
+    fn open_test() {
+        let tablebase = Tablebase::open(FIXTURES).unwrap();
+
+        assert_eq!(tablebase.max_pieces, 3);
+        assert!(Tablebase::open(concat!(env!("CARGO_MANIFEST_DIR"), "/src")).is_err());
+        assert!(Tablebase::open("/does/not/exist").is_err());
+    }
+
+    #[test]
+    fn probe_wdl_test() {
+        let tablebase = Tablebase::open(FIXTURES).unwrap();
+
+        // king and queen against king is a win, for whoever has the queen
+        assert_eq!(tablebase.probe_wdl(&position("8/8/8/4k3/8/8/8/3QK3 w - - 0 1"), 2), Some(Score::tb_win_in(2)));
+        assert_eq!(tablebase.probe_wdl(&position("8/8/8/4k3/8/8/8/3QK3 b - - 0 1"), 2), Some(Score::tb_loss_in(2)));
+
+        // unless the queen hangs
+        assert_eq!(tablebase.probe_wdl(&position("8/8/8/8/8/8/3k4/3Q2K1 b - - 0 1"), 0), Some(Score::DRAW));
+
+        // the defending king gets in front of a rook pawn in time, but not in front of a pawn on the 5th with its king on the 6th
+        assert_eq!(tablebase.probe_wdl(&position("k7/8/8/8/8/8/P7/7K w - - 0 1"), 0), Some(Score::DRAW));
+        assert_eq!(tablebase.probe_wdl(&position("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"), 0), Some(Score::tb_loss_in(0)));
+
+        // a minor piece can't mate
+        assert_eq!(tablebase.probe_wdl(&position("8/8/8/4k3/8/8/8/3NK3 w - - 0 1"), 0), Some(Score::DRAW));
+
+        // too many pieces
+        assert_eq!(tablebase.probe_wdl(&Chess::default(), 0), None);
+        assert_eq!(tablebase.probe_wdl(&position("8/8/8/4k3/8/8/8/2RQK3 w - - 0 1"), 0), None);
+    }
+
+    #[test]
+    fn best_move_test() {
+        let tablebase = Tablebase::open(FIXTURES).unwrap();
+        let game = position("8/8/8/4k3/8/8/8/R3K3 w - - 0 1");
+
+        let (mv, score) = tablebase.best_move(&game).unwrap();
+
+        assert_eq!(score, Score::tb_win_in(0));
+
+        // the move keeps the win
+        let mut after = game.clone();
+        after.play_unchecked(&mv);
+        assert_eq!(tablebase.probe_wdl(&after, 1), Some(Score::tb_loss_in(1)));
+
+        // with the fifty-move counter nearly run out, the same position can only be drawn
+        let (_, score) = tablebase.best_move(&position("8/8/8/4k3/8/8/8/R3K3 w - - 98 100")).unwrap();
+
+        assert_eq!(score, Score::DRAW);
+
+        // mated, so there's nothing to play
+        assert_eq!(tablebase.best_move(&position("k7/2K5/8/8/8/8/8/R7 b - - 0 1")), None);
+    }
+}
@@ -0,0 +1,110 @@
+use std::time::{Duration, Instant};
+
+/// When playing on a clock without moves-to-go, assume this many moves remain
+const DEFAULT_MOVES_TO_GO :u32 = 30;
+
+/// Time lost talking to the GUI, so we never flag because of communication lag
+const MOVE_OVERHEAD :Duration = Duration::from_millis(20);
+
+/// How much time we have to make a move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeBudget {
+    /// Use exactly this much time
+    MoveTime(Duration),
+    /// Our remaining clock, the increment we get after the move, and the moves until the next time control
+    Clock { remaining: Duration, increment: Duration, moves_to_go: Option<u32> },
+}
+
+/// When to stop searching
+///
+/// Once past the soft deadline we won't start another iteration, because it would most likely not
+/// finish in time. At the hard deadline the search is aborted, even in the middle of an iteration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deadlines {
+    pub soft: Instant,
+    pub hard: Instant,
+}
+
+impl TimeBudget {
+    pub fn deadlines(&self, start :Instant) -> Deadlines {
+        match *self {
+            TimeBudget::MoveTime(movetime) => {
+                let hard = start + movetime.saturating_sub(MOVE_OVERHEAD);
+
+                Deadlines { soft: hard, hard }
+            },
+            TimeBudget::Clock { remaining, increment, moves_to_go } => {
+                let remaining = remaining.saturating_sub(MOVE_OVERHEAD);
+                let moves_to_go = moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+
+                // an even share of our clock, plus most of the increment
+                let target = remaining / moves_to_go + increment * 3 / 4;
+
+                // an iteration takes about as long as all the ones before it, so stop at half the target
+                // but let an iteration that has already started run well past the target if needed
+                let soft = (target / 2).min(remaining);
+                let hard = (target * 3).min(remaining / 2).max(soft);
+
+                Deadlines { soft: start + soft, hard: start + hard }
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod time_tests {
+    use std::time::{Duration, Instant};
+
+    use crate::time::TimeBudget;
+
+    /// The soft and hard deadlines, as time from the start of the search
+    fn deadlines(budget :TimeBudget) -> (Duration, Duration) {
+        let start = Instant::now();
+        let deadlines = budget.deadlines(start);
+
+        (deadlines.soft - start, deadlines.hard - start)
+    }
+
+    fn clock(remaining :u64, increment :u64, moves_to_go :Option<u32>) -> TimeBudget {
+        TimeBudget::Clock { remaining: Duration::from_millis(remaining), increment: Duration::from_millis(increment), moves_to_go }
+    }
+
+    fn ms(millis :u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn movetime_test() {
+        // all of the time, less the overhead, and no point starting an iteration we can't finish
+        assert_eq!(deadlines(TimeBudget::MoveTime(ms(1000))), (ms(980), ms(980)));
+        assert_eq!(deadlines(TimeBudget::MoveTime(ms(10))), (ms(0), ms(0)));
+    }
+
+    #[test]
+    fn clock_test() {
+        // 30s after the overhead is 1s a move over 30 moves, plus 3/4 of the increment
+        assert_eq!(deadlines(clock(30_020, 1000, None)), (ms(875), ms(5250)));
+
+        // with moves to go the clock is shared between just those moves
+        assert_eq!(deadlines(clock(10_020, 0, Some(10))), (ms(500), ms(3000)));
+
+        // the last move before the time control never uses more than half the clock
+        assert_eq!(deadlines(clock(1020, 0, Some(1))), (ms(500), ms(500)));
+        assert_eq!(deadlines(clock(1020, 0, Some(0))), (ms(500), ms(500)));
+    }
+
+    #[test]
+    fn low_clock_test() {
+        // nothing left after the overhead
+        assert_eq!(deadlines(clock(15, 0, None)), (ms(0), ms(0)));
+
+        // an increment we don't have yet can't be spent
+        assert_eq!(deadlines(clock(50, 2000, None)), (ms(30), ms(30)));
+
+        // a few milliseconds, the search must stop well before the flag falls
+        let (soft, hard) = deadlines(clock(100, 0, None));
+
+        assert!(soft <= hard && hard <= ms(40));
+    }
+}
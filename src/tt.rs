@@ -0,0 +1,252 @@
+use std::mem::size_of;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use shakmaty::{Chess, Position, Move};
+
+use crate::score::Score;
+
+/// What the stored score tells us about the true score of the position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    /// The score is exact, it was inside the window
+    Exact,
+    /// The search failed high, the true score is at least this
+    Lower,
+    /// The search failed low, the true score is at most this
+    Upper
+}
+
+/// A move squeezed into 15 bits: from, to, and promotion, which is enough to find it again in the legal moves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackedMove(u16);
+
+impl PackedMove {
+    pub fn new(mv :&Move) -> Self {
+        // castling is stored as king takes rook, which is also how shakmaty reports from & to
+        let from = mv.from().map(|sq| sq as u16).unwrap_or(0);
+        let to = mv.to() as u16;
+        let promotion = mv.promotion().map(|role| role as u16).unwrap_or(0);
+
+        PackedMove(from | to << 6 | promotion << 12)
+    }
+
+    #[inline]
+    pub fn matches(&self, mv :&Move) -> bool {
+        PackedMove::new(mv) == *self
+    }
+
+    /// The legal move this was packed from, if there is one in this position
+    pub fn unpack(&self, game :&Chess) -> Option<Move> {
+        game.legal_moves().into_iter().find(|mv| self.matches(mv))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Entry {
+    pub depth: u8,
+    pub bound: Bound,
+    pub score: Score,
+    pub best_move: Option<PackedMove>,
+    generation: u8,
+}
+
+// an entry is packed into a single word: score (32 bits), move (15), depth (7), bound (2), generation (8)
+const MOVE_SHIFT :u32 = 32;
+const DEPTH_SHIFT :u32 = 47;
+const BOUND_SHIFT :u32 = 54;
+const GENERATION_SHIFT :u32 = 56;
+const MAX_STORED_DEPTH :usize = 0x7F;
+
+impl Entry {
+    fn pack(&self) -> u64 {
+        let best_move = self.best_move.map(|mv| mv.0).unwrap_or(0) as u64;
+        let bound = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2
+        };
+
+        self.score.raw() as u32 as u64
+            | best_move << MOVE_SHIFT
+            | (self.depth as u64) << DEPTH_SHIFT
+            | bound << BOUND_SHIFT
+            | (self.generation as u64) << GENERATION_SHIFT
+    }
+
+    fn unpack(data :u64) -> Self {
+        let best_move = ((data >> MOVE_SHIFT) & 0x7FFF) as u16;
+        let bound = match (data >> BOUND_SHIFT) & 0x3 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper
+        };
+
+        Entry {
+            depth: ((data >> DEPTH_SHIFT) & MAX_STORED_DEPTH as u64) as u8,
+            bound,
+            score: Score::from_raw(data as u32 as i32),
+            best_move: if best_move == 0 { None } else { Some(PackedMove(best_move)) },
+            generation: (data >> GENERATION_SHIFT) as u8
+        }
+    }
+}
+
+/// A slot holds the entry, and the hash xor the entry, so a slot torn by two threads writing at once
+/// no longer matches the hash and is ignored
+#[derive(Default)]
+struct Slot {
+    check: AtomicU64,
+    data: AtomicU64,
+}
+
+/// A fixed-size transposition table indexed by Zobrist hash, shared without locks between search threads
+///
+/// Replacement policy: each hash maps to a single slot, and a new entry replaces the old one when
+/// the slot is empty, holds the same position, was written during a previous search, or was
+/// searched to the same or a shallower depth. Otherwise the deeper, current entry is kept.
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+    mask: usize,
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    /// Creates a table using at most size_mb megabytes, a size of 0 disables the table
+    pub fn new(size_mb :usize) -> Self {
+        let max_entries = size_mb * 1024 * 1024 / size_of::<Slot>();
+
+        // round down to a power of 2 so we can mask instead of mod
+        let num_entries :usize = if max_entries == 0 { 0 } else { 1 << (usize::BITS - 1 - max_entries.leading_zeros()) };
+
+        TranspositionTable {
+            slots: (0..num_entries).map(|_| Slot::default()).collect(),
+            mask: num_entries.saturating_sub(1),
+            generation: AtomicU8::new(0)
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.slots.is_empty()
+    }
+
+    /// Removes all entries, used when starting a new game
+    pub fn clear(&self) {
+        for slot in &self.slots {
+            slot.check.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+
+        self.generation.store(0, Ordering::Relaxed);
+    }
+
+    /// Marks the start of a new search, so entries from older searches can be replaced
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Looks up the entry for this hash, if there is one
+    pub fn probe(&self, key :u64) -> Option<Entry> {
+        if !self.is_enabled() {
+            return None;
+        }
+
+        let slot = &self.slots[key as usize & self.mask];
+        let data = slot.data.load(Ordering::Relaxed);
+
+        if data != 0 && slot.check.load(Ordering::Relaxed) ^ data == key {
+            Some(Entry::unpack(data))
+        } else {
+            None
+        }
+    }
+
+    /// Stores a search result, returns false if the slot's current entry was kept instead
+    pub fn store(&self, key :u64, depth :usize, bound :Bound, score :Score, best_move :Option<&Move>) -> bool {
+        if !self.is_enabled() {
+            return false;
+        }
+
+        let generation = self.generation.load(Ordering::Relaxed);
+        let depth = depth.min(MAX_STORED_DEPTH) as u8;
+        let slot = &self.slots[key as usize & self.mask];
+
+        let old_data = slot.data.load(Ordering::Relaxed);
+        let old = Entry::unpack(old_data);
+        let same_key = slot.check.load(Ordering::Relaxed) ^ old_data == key;
+
+        if !(old_data == 0 || same_key || old.generation != generation || depth >= old.depth) {
+            return false;
+        }
+
+        // keep the old best move if we don't have one for the same position
+        let best_move = match best_move {
+            Some(mv) => Some(PackedMove::new(mv)),
+            None if same_key => old.best_move,
+            None => None
+        };
+
+        let data = Entry { depth, bound, score, best_move, generation }.pack();
+
+        slot.check.store(key ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+
+        true
+    }
+
+    /// How full the table is in permill, sampled from the first 1000 slots like other engines do
+    pub fn hashfull(&self) -> u16 {
+        let sample = self.slots.len().min(1000);
+
+        if sample == 0 {
+            return 0;
+        }
+
+        let generation = self.generation.load(Ordering::Relaxed);
+        let used = self.slots[..sample].iter().filter(|slot| {
+            let data = slot.data.load(Ordering::Relaxed);
+
+            data != 0 && Entry::unpack(data).generation == generation
+        }).count();
+
+        (used * 1000 / sample) as u16
+    }
+}
+
+
+#[cfg(test)]
+mod tt_tests {
+    use shakmaty::{Chess, Position, CastlingMode};
+    use shakmaty::fen::Fen;
+
+    use crate::score::Score;
+    use crate::tt::{TranspositionTable, Bound, PackedMove};
+
+    #[test]
+    fn store_probe_test() {
+        let tt = TranspositionTable::new(1);
+        let game :Chess = "r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1".parse::<Fen>().unwrap().position(CastlingMode::Standard).unwrap();
+
+        // every move, including castling and promotions, packs into something we can find again
+        for mv in game.legal_moves() {
+            assert_eq!(PackedMove::new(&mv).unpack(&game), Some(mv.clone()));
+        }
+
+        let mv = game.legal_moves()[0].clone();
+
+        tt.store(42, 5, Bound::Lower, Score::cp(-123), Some(&mv));
+        let entry = tt.probe(42).unwrap();
+
+        assert_eq!((entry.depth, entry.bound, entry.score), (5, Bound::Lower, Score::cp(-123)));
+        assert!(entry.best_move.unwrap().matches(&mv));
+        assert!(tt.probe(43).is_none());
+
+        // mate scores survive being packed
+        for score in [Score::mate_in(3), Score::mated_in(4)] {
+            tt.store(42, 6, Bound::Exact, score, None);
+            assert_eq!(tt.probe(42).unwrap().score, score);
+        }
+
+        // the best move is kept when the new entry doesn't have one
+        assert!(tt.probe(42).unwrap().best_move.unwrap().matches(&mv));
+    }
+}
@@ -0,0 +1,158 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::time::Instant;
+
+use rayon::prelude::*;
+
+use shakmaty::Chess;
+
+use crate::bench::read_labeled_fens;
+use crate::eval::evaluate_with;
+use crate::weights::Weights;
+
+/// Scores are turned into a win probability with 1 / (1 + 10^(-score / SIGMOID_SCALE)),
+/// so being a lot better counts for less than the difference between equal and a little better
+const SIGMOID_SCALE :f64 = 400.0;
+
+/// Every nth position is held back to check that the tuned weights aren't just fit to the training set
+const VALIDATION_EVERY :usize = 10;
+
+/// How far each weight is nudged at a time, in centipawns
+const STEP :i64 = 1;
+
+/// A weight in the weights: the term, the index in the term, and the middlegame (0) or endgame (1) value
+type Param = (usize, usize, usize);
+
+/// Texel tuning: coordinate descent on the error between the sigmoid of our static eval and the
+/// sigmoid of the label, for every weight of the hand written evaluation
+///
+/// The weights are written to out_path after each pass, so a run can be stopped at any time, and
+/// the training and validation error after each pass are written to out_path.errors.csv
+pub fn tune(path :&str, out_path :&str, passes :usize, initial :Weights) {
+    let (train, validation) :(Vec<_>, Vec<_>) = read_labeled_fens(path).into_iter()
+        .enumerate()
+        .partition(|(i, _)| i % VALIDATION_EVERY != 0);
+
+    let train = train.into_iter().map(|(_, position)| position).collect::<Vec<_>>();
+    let validation = validation.into_iter().map(|(_, position)| position).collect::<Vec<_>>();
+
+    println!("{} training positions, {} validation positions", train.len(), validation.len());
+
+    let curves_path = format!("{}.errors.csv", out_path);
+    let mut curves = File::create(&curves_path).expect("Error creating error curves file");
+    writeln!(curves, "pass,train,validation").expect("Error writing error curves file");
+
+    let mut weights = initial;
+    let mut train_error = error(&weights, &train);
+
+    println!("{:>4} {:>12} {:>12} {:>8} {:>8}", "PASS", "TRAIN", "VALIDATION", "CHANGED", "TIME");
+
+    for pass in 0..=passes {
+        let start = Instant::now();
+
+        // pass 0 is just the starting point
+        let changed = if pass == 0 { 0 } else { tune_pass(&mut weights, &train, &mut train_error) };
+        let validation_error = error(&weights, &validation);
+
+        println!("{:>4} {:>12.8} {:>12.8} {:>8} {:>7.1}s", pass, train_error, validation_error, changed, start.elapsed().as_secs_f64());
+
+        writeln!(curves, "{},{},{}", pass, train_error, validation_error).expect("Error writing error curves file");
+        fs::write(out_path, weights.to_text()).expect("Error writing weights file");
+
+        if pass > 0 && changed == 0 {
+            println!("No weight changed, done");
+            break;
+        }
+    }
+
+    println!("Wrote {} and {}", out_path, curves_path);
+}
+
+/// One pass of coordinate descent: tries each weight one step up, then one step down, and keeps
+/// whichever lowers the training error. Returns how many weights changed.
+fn tune_pass(weights :&mut Weights, positions :&[(i64, Chess)], best_error :&mut f64) -> usize {
+    let mut changed = 0;
+
+    for param in params(weights) {
+        let original = *value(weights, param);
+
+        for delta in [STEP, -STEP] {
+            *value(weights, param) = original + delta;
+
+            let new_error = error(weights, positions);
+
+            if new_error < *best_error {
+                *best_error = new_error;
+                changed += 1;
+                break;
+            }
+
+            *value(weights, param) = original;
+        }
+    }
+
+    changed
+}
+
+/// Mean squared difference between the win probabilities of our eval and the labels, computed in parallel
+pub fn error(weights :&Weights, positions :&[(i64, Chess)]) -> f64 {
+    if positions.is_empty() {
+        return 0.0;
+    }
+
+    let total = positions.par_iter().map(|(label, game)| {
+        let diff = sigmoid(evaluate_with(weights, game) as f64) - sigmoid(*label as f64);
+
+        diff * diff
+    }).sum::<f64>();
+
+    total / positions.len() as f64
+}
+
+#[inline]
+fn sigmoid(score :f64) -> f64 {
+    1.0 / (1.0 + 10_f64.powf(-score / SIGMOID_SCALE))
+}
+
+fn params(weights :&Weights) -> Vec<Param> {
+    weights.terms().iter().enumerate()
+        .flat_map(|(term, (_, values))| (0..values.len()).flat_map(move |i| [(term, i, 0), (term, i, 1)]))
+        .collect()
+}
+
+fn value(weights :&mut Weights, (term, index, phase) :Param) -> &mut i64 {
+    let tapered = &mut weights.terms_mut().swap_remove(term).1[index];
+
+    if phase == 0 { &mut tapered.0 } else { &mut tapered.1 }
+}
+
+
+#[cfg(test)]
+mod tune_tests {
+    use shakmaty::{Chess, CastlingMode};
+    use shakmaty::fen::Fen;
+
+    use crate::eval::BUILTIN_WEIGHTS;
+    use crate::tune::{error, tune_pass, sigmoid};
+
+    #[test]
+    fn tune_pass_test() {
+        assert_eq!(sigmoid(0.0), 0.5);
+        assert!((sigmoid(400.0) - 10.0 / 11.0).abs() < 1e-9);
+
+        // the labels say the extra knight is worth a lot more than we think
+        let positions = ["4k3/8/8/8/8/8/8/1N2K3 w - - 0 1", "4k3/8/8/8/8/8/8/1N2K3 b - - 0 1", "1n2k3/8/8/8/8/8/8/4K3 w - - 0 1"].iter()
+            .zip([800, -800, -800])
+            .map(|(fen, label)| (label, fen.parse::<Fen>().unwrap().position::<Chess>(CastlingMode::Standard).unwrap()))
+            .collect::<Vec<_>>();
+
+        let mut weights = BUILTIN_WEIGHTS;
+        let mut best_error = error(&weights, &positions);
+        let start_error = best_error;
+
+        assert!(tune_pass(&mut weights, &positions, &mut best_error) > 0);
+        assert!(best_error < start_error);
+        assert_eq!(best_error, error(&weights, &positions));
+        assert!(weights.material[1].1 > BUILTIN_WEIGHTS.material[1].1);
+    }
+}
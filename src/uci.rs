@@ -0,0 +1,800 @@
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(test)]
+use std::sync::mpsc::Sender;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use shakmaty::{Chess, Position, Setup, Move, Role, Square, CastlingMode};
+use shakmaty::fen::Fen;
+use shakmaty::uci::Uci as ShakmatyUci;
+
+use vampirc_uci::{UciMessage, UciMove, UciSquare, UciPiece, UciInfoAttribute, UciTimeControl, UciSearchControl, UciOptionConfig, parse_one};
+
+use crate::book::{Book, BookSelection, double_push_square};
+use crate::eval::BUILTIN_WEIGHTS;
+use crate::evaluator::{Evaluator, PstEvaluator, EVALUATORS, load_evaluator};
+use crate::result::SearchResult;
+use crate::score::Score;
+use crate::search::{SearchContext, SearchLimits, SearchOptions, Algorithm};
+use crate::tablebase::Tablebase;
+use crate::time::TimeBudget;
+use crate::tt::TranspositionTable;
+use crate::weights::Weights;
+use crate::zobrist;
+
+/// Size of the transposition table in MB
+const DEFAULT_HASH_MB :usize = 16;
+const MAX_HASH_MB :usize = 65536;
+
+/// A single thread searches deterministically, which makes debugging a lot easier
+const DEFAULT_THREADS :usize = 1;
+const MAX_THREADS :usize = 256;
+
+/// How many plies into the game we play from the opening book
+const DEFAULT_BOOK_DEPTH :usize = 20;
+const MAX_BOOK_DEPTH :usize = 255;
+
+/// A UCI engine server: reads commands on STDIN and writes responses to STDOUT
+pub struct UciEngine {
+    game: Chess,
+    /// Hashes of the positions before the current one, to detect repetitions
+    history: Vec<u64>,
+    /// The square the last move's double push skipped, the book hashes it even when shakmaty's Chess hides it
+    ep_square: Option<Square>,
+    algorithm: Algorithm,
+    options: SearchOptions,
+    threads: usize,
+    evaluator: Arc<dyn Evaluator>,
+    evaluator_name: String,
+    /// Weights for the learned evaluators
+    evaluator_file: Option<String>,
+    /// The evaluator and file the GUI asked for last, the learned ones only load once both are set
+    requested_evaluator: (String, Option<String>),
+    /// Weights for the hand written evaluator
+    weights: Weights,
+    book: Option<Book>,
+    book_selection: BookSelection,
+    book_depth: usize,
+    tablebase: Option<Arc<Tablebase>>,
+    tt: Arc<TranspositionTable>,
+    stop: Arc<AtomicBool>,
+    /// Set when the GUI played the move we were pondering on, so that search ends without a best move
+    ponderhit: Arc<AtomicBool>,
+    /// The limits of a go ponder, which apply once we get ponderhit
+    ponder_limits: Option<SearchLimits>,
+    search_thread: Option<JoinHandle<()>>,
+    output: Output,
+}
+
+impl UciEngine {
+    pub fn new() -> Self {
+        UciEngine {
+            game: Chess::default(),
+            history: Vec::new(),
+            ep_square: None,
+            algorithm: Algorithm::default(),
+            options: SearchOptions::default(),
+            threads: DEFAULT_THREADS,
+            evaluator: Arc::new(PstEvaluator::default()),
+            evaluator_name: EVALUATORS[0].to_string(),
+            evaluator_file: None,
+            requested_evaluator: (EVALUATORS[0].to_string(), None),
+            weights: BUILTIN_WEIGHTS,
+            book: None,
+            book_selection: BookSelection::Weighted,
+            book_depth: DEFAULT_BOOK_DEPTH,
+            tablebase: None,
+            tt: Arc::new(TranspositionTable::new(DEFAULT_HASH_MB)),
+            stop: Arc::new(AtomicBool::new(false)),
+            ponderhit: Arc::new(AtomicBool::new(false)),
+            ponder_limits: None,
+            search_thread: None,
+            output: Output::Stdout
+        }
+    }
+
+    /// Switches to one of the EVALUATORS, keeping the current one if it can't be loaded
+    pub fn set_evaluator(&mut self, name :&str, path :Option<&str>) -> Result<(), String> {
+        self.stop_search();
+        self.evaluator = load_evaluator(name, path, &self.weights)?;
+
+        // only once it's loaded, so we never report an evaluator we aren't using
+        self.evaluator_name = name.to_lowercase();
+        self.evaluator_file = path.map(|p| p.to_string());
+
+        Ok(())
+    }
+
+    /// Tries the evaluator and file the GUI asked for, telling it which evaluator is still in use when that fails
+    fn load_requested_evaluator(&mut self) {
+        let (name, path) = self.requested_evaluator.clone();
+
+        if let Err(e) = self.set_evaluator(&name, path.as_deref()) {
+            self.output.send_string(&format!("{}, still using the {} evaluator", e, self.evaluator_name));
+        }
+    }
+
+    /// Loads the hand written evaluator's weights, anything not in the file keeps its built-in value
+    pub fn set_eval_file(&mut self, path :Option<&str>) -> Result<(), String> {
+        let weights = match path {
+            Some(path) => Weights::load(path, &BUILTIN_WEIGHTS)?,
+            None => BUILTIN_WEIGHTS
+        };
+        let previous = std::mem::replace(&mut self.weights, weights);
+        let (name, path) = (self.evaluator_name.clone(), self.evaluator_file.clone());
+
+        if let Err(e) = self.set_evaluator(&name, path.as_deref()) {
+            self.weights = previous;
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// Opens a Polyglot book, no file turns the book off
+    pub fn set_book(&mut self, path :Option<&str>) -> Result<(), String> {
+        self.book = None;
+
+        if let Some(path) = path {
+            self.book = Some(Book::open(path)?);
+        }
+
+        Ok(())
+    }
+
+    pub fn set_algorithm(&mut self, algorithm :Algorithm) {
+        self.algorithm = algorithm;
+    }
+
+    /// Replaces the transposition table with an empty one of the given size
+    pub fn set_hash(&mut self, size_mb :usize) -> Result<(), String> {
+        if size_mb > MAX_HASH_MB {
+            return Err(format!("Invalid hash size: {}", size_mb));
+        }
+
+        self.stop_search();
+        self.tt = Arc::new(TranspositionTable::new(size_mb));
+
+        Ok(())
+    }
+
+    pub fn set_threads(&mut self, threads :usize) -> Result<(), String> {
+        if !(1..=MAX_THREADS).contains(&threads) {
+            return Err(format!("Invalid number of threads: {}", threads));
+        }
+
+        self.threads = threads;
+
+        Ok(())
+    }
+
+    /// Opens the Syzygy tables in the directories of the path, no path turns the tablebases off
+    pub fn set_tablebase(&mut self, path :Option<&str>) -> Result<(), String> {
+        self.stop_search();
+        self.tablebase = None;
+
+        if let Some(path) = path {
+            self.tablebase = Some(Arc::new(Tablebase::open(path)?));
+        }
+
+        Ok(())
+    }
+
+    /// Process commands until we get quit, or STDIN is closed
+    pub fn run(&mut self) {
+        let stdin = io::stdin();
+
+        for line in stdin.lock().lines() {
+            let line = line.expect("Error reading STDIN");
+
+            if line.trim().is_empty() {
+                continue
+            }
+
+            if !self.handle(&line) {
+                break
+            }
+        }
+
+        self.stop_search();
+    }
+
+    /// Handles a single line from the GUI, returns false when we should quit
+    fn handle(&mut self, line :&str) -> bool {
+        match parse_one(line) {
+            UciMessage::Uci => {
+                self.output.send(UciMessage::Id { name: Some(format!("fishermann {}", env!("CARGO_PKG_VERSION"))), author: None });
+                self.output.send(UciMessage::Id { name: None, author: Some(env!("CARGO_PKG_AUTHORS").to_string()) });
+                self.output.send(UciMessage::Option(UciOptionConfig::Combo {
+                    name: "Algorithm".to_string(),
+                    default: Some(Algorithm::default().name().to_string()),
+                    var: Algorithm::ALL.iter().map(|a| a.name().to_string()).collect()
+                }));
+                self.output.send(UciMessage::Option(UciOptionConfig::Check {
+                    name: "Quiescence".to_string(),
+                    default: Some(SearchOptions::default().quiescence)
+                }));
+                self.output.send(UciMessage::Option(UciOptionConfig::Check {
+                    name: "QuiescenceChecks".to_string(),
+                    default: Some(SearchOptions::default().qs_checks)
+                }));
+                self.output.send(UciMessage::Option(UciOptionConfig::Check {
+                    name: "DeltaPruning".to_string(),
+                    default: Some(SearchOptions::default().delta_pruning)
+                }));
+                self.output.send(UciMessage::Option(UciOptionConfig::Check {
+                    name: "SEE".to_string(),
+                    default: Some(SearchOptions::default().see)
+                }));
+                self.output.send(UciMessage::Option(UciOptionConfig::Check {
+                    name: "NullMove".to_string(),
+                    default: Some(SearchOptions::default().null_move)
+                }));
+                self.output.send(spin_option("NullMoveReduction", SearchOptions::default().null_move_reduction, 1, 6));
+                self.output.send(UciMessage::Option(UciOptionConfig::Check {
+                    name: "LMR".to_string(),
+                    default: Some(SearchOptions::default().lmr)
+                }));
+                self.output.send(spin_option("LMRFullMoves", SearchOptions::default().lmr_full_moves, 1, 64));
+                self.output.send(spin_option("LMRMinDepth", SearchOptions::default().lmr_min_depth, 1, 32));
+                self.output.send(spin_option("LMRReduction", SearchOptions::default().lmr_reduction, 1, 6));
+                self.output.send(UciMessage::Option(UciOptionConfig::Check {
+                    name: "Aspiration".to_string(),
+                    default: Some(SearchOptions::default().aspiration)
+                }));
+                self.output.send(spin_option("AspirationWindow", SearchOptions::default().aspiration_window as usize, 1, 1000));
+                self.output.send(UciMessage::Option(UciOptionConfig::Combo {
+                    name: "Evaluator".to_string(),
+                    default: Some(EVALUATORS[0].to_string()),
+                    var: EVALUATORS.iter().map(|e| e.to_string()).collect()
+                }));
+                self.output.send(UciMessage::Option(UciOptionConfig::String {
+                    name: "EvaluatorFile".to_string(),
+                    default: None
+                }));
+                self.output.send(UciMessage::Option(UciOptionConfig::String {
+                    name: "EvalFile".to_string(),
+                    default: None
+                }));
+                self.output.send(UciMessage::Option(UciOptionConfig::String {
+                    name: "BookFile".to_string(),
+                    default: None
+                }));
+                self.output.send(UciMessage::Option(UciOptionConfig::Combo {
+                    name: "BookSelection".to_string(),
+                    default: Some(BookSelection::Weighted.name().to_string()),
+                    var: BookSelection::ALL.iter().map(|s| s.name().to_string()).collect()
+                }));
+                self.output.send(spin_option("BookDepth", DEFAULT_BOOK_DEPTH, 0, MAX_BOOK_DEPTH));
+                self.output.send(UciMessage::Option(UciOptionConfig::String {
+                    name: "SyzygyPath".to_string(),
+                    default: None
+                }));
+                self.output.send(UciMessage::Option(UciOptionConfig::Check {
+                    name: "Ponder".to_string(),
+                    default: Some(false)
+                }));
+                self.output.send(UciMessage::Option(UciOptionConfig::Spin {
+                    name: "Hash".to_string(),
+                    default: Some(DEFAULT_HASH_MB as i64),
+                    min: Some(0),
+                    max: Some(MAX_HASH_MB as i64)
+                }));
+                self.output.send(UciMessage::Option(UciOptionConfig::Spin {
+                    name: "Threads".to_string(),
+                    default: Some(DEFAULT_THREADS as i64),
+                    min: Some(1),
+                    max: Some(MAX_THREADS as i64)
+                }));
+                self.output.send(UciMessage::UciOk);
+            },
+            UciMessage::IsReady => self.output.send(UciMessage::ReadyOk),
+            UciMessage::SetOption { name, value } => self.set_option(&name, value),
+            UciMessage::UciNewGame => {
+                self.stop_search();
+                self.game = Chess::default();
+                self.history.clear();
+                self.ep_square = None;
+                self.tt.clear();
+            },
+            UciMessage::Position { startpos, fen, moves } => {
+                self.stop_search();
+
+                match set_position(startpos, fen.map(|f| f.0), &moves) {
+                    Ok((game, history, ep_square)) => {
+                        self.game = game;
+                        self.history = history;
+                        self.ep_square = ep_square;
+                    },
+                    Err(e) => self.output.send_string(&e)
+                }
+            },
+            UciMessage::Go { time_control, search_control } => {
+                self.stop_search();
+
+                let limits = search_limits(&self.game, time_control, search_control);
+
+                // the parser drops ponder when the clock is given too, so we look for it ourselves
+                if line.split_whitespace().any(|word| word == "ponder") {
+                    self.go(SearchLimits { infinite: true, ..SearchLimits::default() });
+                    self.ponder_limits = Some(limits);
+                } else {
+                    self.go(limits);
+                }
+            },
+            UciMessage::PonderHit => {
+                if let Some(limits) = self.ponder_limits.take() {
+                    self.ponderhit.store(true, Ordering::Relaxed);
+                    self.stop_search();
+                    self.go(limits);
+                }
+            },
+            UciMessage::Stop => self.stop_search(),
+            UciMessage::Quit => return false,
+            UciMessage::Unknown(line, _) => self.output.send_string(&format!("Unknown command: {}", line.trim())),
+            _ => ()
+        }
+
+        true
+    }
+
+    fn set_option(&mut self, name :&str, value :Option<String>) {
+        match (name.to_lowercase().as_str(), value) {
+            ("algorithm", Some(value)) => {
+                match Algorithm::from_name(&value) {
+                    Some(algorithm) => self.set_algorithm(algorithm),
+                    None => self.output.send_string(&format!("Unknown algorithm: {}", value))
+                }
+            },
+            // the GUI can send the evaluator and its file in either order, so we try again after each
+            ("evaluator", Some(value)) => {
+                self.requested_evaluator.0 = value;
+                self.load_requested_evaluator();
+            },
+            ("evaluatorfile", value) => {
+                self.requested_evaluator.1 = value.filter(|v| !v.is_empty() && v != "<empty>");
+                self.load_requested_evaluator();
+            },
+            ("evalfile", value) => {
+                let path = value.filter(|v| !v.is_empty() && v != "<empty>");
+
+                if let Err(e) = self.set_eval_file(path.as_deref()) {
+                    self.output.send_string(&e);
+                }
+            },
+            ("bookfile", value) => {
+                let path = value.filter(|v| !v.is_empty() && v != "<empty>");
+
+                if let Err(e) = self.set_book(path.as_deref()) {
+                    self.output.send_string(&e);
+                }
+            },
+            ("bookselection", Some(value)) => {
+                match BookSelection::from_name(&value) {
+                    Some(selection) => self.book_selection = selection,
+                    None => self.output.send_string(&format!("Unknown book selection: {}", value))
+                }
+            },
+            ("bookdepth", Some(value)) => set_spin(&mut self.book_depth, &value, 0, MAX_BOOK_DEPTH, &self.output),
+            ("syzygypath", value) => {
+                let path = value.filter(|v| !v.is_empty() && v != "<empty>");
+
+                if let Err(e) = self.set_tablebase(path.as_deref()) {
+                    self.output.send_string(&e);
+                }
+            },
+            ("hash", Some(value)) => {
+                let result = value.parse::<usize>().map_err(|_| format!("Invalid hash size: {}", value)).and_then(|size_mb| self.set_hash(size_mb));
+
+                if let Err(e) = result {
+                    self.output.send_string(&e);
+                }
+            },
+            ("threads", Some(value)) => {
+                let result = value.parse::<usize>().map_err(|_| format!("Invalid number of threads: {}", value)).and_then(|threads| self.set_threads(threads));
+
+                if let Err(e) = result {
+                    self.output.send_string(&e);
+                }
+            },
+            ("quiescence", Some(value)) => self.options.quiescence = value == "true",
+            ("quiescencechecks", Some(value)) => self.options.qs_checks = value == "true",
+            ("deltapruning", Some(value)) => self.options.delta_pruning = value == "true",
+            ("see", Some(value)) => self.options.see = value == "true",
+            ("nullmove", Some(value)) => self.options.null_move = value == "true",
+            ("nullmovereduction", Some(value)) => set_spin(&mut self.options.null_move_reduction, &value, 1, 6, &self.output),
+            ("lmr", Some(value)) => self.options.lmr = value == "true",
+            ("lmrfullmoves", Some(value)) => set_spin(&mut self.options.lmr_full_moves, &value, 1, 64, &self.output),
+            ("lmrmindepth", Some(value)) => set_spin(&mut self.options.lmr_min_depth, &value, 1, 32, &self.output),
+            ("lmrreduction", Some(value)) => set_spin(&mut self.options.lmr_reduction, &value, 1, 6, &self.output),
+            ("aspiration", Some(value)) => self.options.aspiration = value == "true",
+            // the GUI tells us whether it will send go ponder, we're ready either way
+            ("ponder", _) => (),
+            ("aspirationwindow", Some(value)) => set_spin(&mut self.options.aspiration_window, &value, 1, 1000, &self.output),
+            (_, _) => self.output.send_string(&format!("Unknown option: {}", name))
+        }
+    }
+
+    /// Starts searching the current position on a background thread, unless the book or the tablebases have a move for it
+    fn go(&mut self, limits :SearchLimits) {
+        if !limits.infinite {
+            if let Some(mv) = self.book_move() {
+                self.output.send_string("book move");
+                self.output.send(UciMessage::BestMove { best_move: to_uci_move(&mv), ponder: None });
+                return;
+            }
+
+            // the tables know the result, and the move that keeps it under the fifty-move rule
+            if let Some((mv, score)) = self.tablebase.as_ref().and_then(|tb| tb.best_move(&self.game)) {
+                self.output.send(UciMessage::Info(vec![
+                    score_attribute(score),
+                    UciInfoAttribute::TbHits(1),
+                    UciInfoAttribute::Pv(vec![to_uci_move(&mv)])
+                ]));
+                self.output.send(UciMessage::BestMove { best_move: to_uci_move(&mv), ponder: None });
+                return;
+            }
+        }
+
+        let game = self.game.clone();
+        let history = self.history.clone();
+        let algorithm = self.algorithm;
+        let options = self.options.clone();
+        let threads = self.threads;
+        let tt = self.tt.clone();
+        let evaluator = self.evaluator.clone();
+        let tablebase = self.tablebase.clone();
+        let output = self.output.clone();
+
+        // each search gets fresh stop and ponderhit flags
+        self.stop = Arc::new(AtomicBool::new(false));
+        self.ponderhit = Arc::new(AtomicBool::new(false));
+        let stop = self.stop.clone();
+        let ponderhit = self.ponderhit.clone();
+
+        self.search_thread = Some(thread::spawn(move || {
+            run_search(&game, history, algorithm, options, threads, &limits, stop, ponderhit, &tt, &evaluator, tablebase.as_deref(), &output);
+        }));
+    }
+
+    /// A move from the book, if we have one and are still in the opening
+    fn book_move(&self) -> Option<Move> {
+        let book = self.book.as_ref()?;
+        let ply = 2 * (self.game.fullmoves().get() as usize - 1) + self.game.turn().is_black() as usize;
+
+        if ply >= self.book_depth {
+            return None;
+        }
+
+        let random = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0);
+
+        book.pick(&self.game, self.ep_square, self.book_selection, random)
+    }
+
+    /// Tells any running search to stop, and waits for it to report its best move
+    fn stop_search(&mut self) {
+        self.ponder_limits = None;
+        self.stop.store(true, Ordering::Relaxed);
+
+        if let Some(handle) = self.search_thread.take() {
+            handle.join().expect("Error joining search thread");
+        }
+    }
+}
+
+/// Sets up a game from either the start position or a FEN, then plays the moves
+/// Also returns the hashes of the positions the moves were played from, and the square a double push skipped last move
+fn set_position(startpos :bool, fen :Option<String>, moves :&[UciMove]) -> Result<(Chess, Vec<u64>, Option<Square>), String> {
+    let (mut game, mut ep_square) = match fen {
+        Some(fen) if !startpos => {
+            let setup :Fen = fen.parse().map_err(|_| format!("Error parsing FEN: {}", fen))?;
+
+            (setup.position(CastlingMode::Standard).map_err(|_| format!("Illegal position: {}", fen))?, setup.ep_square)
+        },
+        _ => (Chess::default(), None)
+    };
+    let mut history = Vec::with_capacity(moves.len());
+
+    for uci_move in moves {
+        let mv = ShakmatyUci::from_ascii(uci_move.to_string().as_bytes())
+            .ok()
+            .and_then(|uci| uci.to_move(&game).ok())
+            .ok_or_else(|| format!("Illegal move: {}", uci_move))?;
+
+        history.push(zobrist::hash(&game));
+        game.play_unchecked(&mv);
+        ep_square = double_push_square(&mv);
+    }
+
+    Ok((game, history, ep_square))
+}
+
+/// Converts the go command into limits for the search
+fn search_limits(game :&Chess, time_control :Option<UciTimeControl>, search_control :Option<UciSearchControl>) -> SearchLimits {
+    let mut limits = SearchLimits::default();
+
+    match time_control {
+        Some(UciTimeControl::Infinite) | Some(UciTimeControl::Ponder) => limits.infinite = true,
+        Some(UciTimeControl::MoveTime(movetime)) => limits.time = Some(TimeBudget::MoveTime(to_std(movetime))),
+        Some(UciTimeControl::TimeLeft { white_time, black_time, white_increment, black_increment, moves_to_go }) => {
+            let (time, inc) = if game.turn().is_white() {
+                (white_time, white_increment)
+            } else {
+                (black_time, black_increment)
+            };
+
+            limits.time = time.map(|time| TimeBudget::Clock {
+                remaining: to_std(time),
+                increment: inc.map(to_std).unwrap_or_default(),
+                moves_to_go: moves_to_go.map(|m| m as u32)
+            });
+        },
+        None => ()
+    }
+
+    if let Some(search_control) = search_control {
+        limits.depth = search_control.depth.map(|d| d as usize);
+        limits.nodes = search_control.nodes;
+    }
+
+    limits
+}
+
+/// Iteratively searches deeper until we run out of depth, time, or nodes, then reports the best move
+#[allow(clippy::too_many_arguments)]
+fn run_search<E :Evaluator>(game :&Chess, history :Vec<u64>, algorithm :Algorithm, options :SearchOptions, threads :usize, limits :&SearchLimits, stop :Arc<AtomicBool>, ponderhit :Arc<AtomicBool>, tt :&TranspositionTable, eval :&E, tablebase :Option<&Tablebase>, output :&Output) {
+    let start = Instant::now();
+
+    tt.new_search();
+
+    let mut ctx = SearchContext::new(stop.clone(), tt, eval, options);
+    ctx.set_history(history);
+    ctx.set_tablebase(tablebase);
+
+    let result = SearchResult::search_with(&mut ctx, threads, algorithm, game, limits, |ctx, depth, score, moves| {
+        // the moves come back from the search with the root move last
+        let pv = moves.iter().rev().map(to_uci_move).collect::<Vec<_>>();
+        let elapsed = start.elapsed();
+        let nodes = ctx.total_nodes();
+
+        output.send(UciMessage::Info(vec![
+            UciInfoAttribute::Depth(depth as u8),
+            UciInfoAttribute::SelDepth(ctx.stats.seldepth.min(u8::MAX as usize) as u8),
+            score_attribute(score),
+            UciInfoAttribute::Nodes(nodes),
+            UciInfoAttribute::Nps((nodes as f64 / elapsed.as_secs_f64().max(0.001)) as u64),
+            UciInfoAttribute::Time(vampirc_uci::Duration::milliseconds(elapsed.as_millis() as i64)),
+            UciInfoAttribute::HashFull(ctx.tt.hashfull()),
+            UciInfoAttribute::TbHits(ctx.stats.tb_hits),
+            UciInfoAttribute::Pv(pv)
+        ]));
+    });
+
+    // the statistics UCI has no attributes for
+    output.send_string(&format!("qnodes {} tthits {} cutoffs {} first {:.1}% ebf {:.2}",
+                                result.qnodes,
+                                result.tt_hits,
+                                result.beta_cutoffs,
+                                result.first_move_rate() * 100.0,
+                                result.branching_factor));
+
+    let best_move = result.best_move().cloned();
+    // the reply we expect, for the GUI to ponder on
+    let ponder_move = result.pv.get(1).map(to_uci_move);
+
+    // in infinite mode we cannot report a best move until told to stop
+    if limits.infinite {
+        while !stop.load(Ordering::Relaxed) {
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        // the GUI played the move we were pondering on, a new search with the real limits reports the move instead
+        if ponderhit.load(Ordering::Relaxed) {
+            return;
+        }
+    }
+
+    // if we didn't finish a single iteration, then any legal move is better than nothing
+    let best_move = best_move.or_else(|| game.legal_moves().first().cloned());
+
+    match best_move {
+        Some(mv) => output.send(UciMessage::BestMove { best_move: to_uci_move(&mv), ponder: ponder_move }),
+        None => output.send_line("bestmove 0000") // no legal moves, the game is over
+    }
+}
+
+fn spin_option(name :&str, default :usize, min :usize, max :usize) -> UciMessage {
+    UciMessage::Option(UciOptionConfig::Spin {
+        name: name.to_string(),
+        default: Some(default as i64),
+        min: Some(min as i64),
+        max: Some(max as i64)
+    })
+}
+
+/// Sets a search parameter from a spin option, if the value is in range
+fn set_spin<T :FromStr + PartialOrd>(param :&mut T, value :&str, min :T, max :T, output :&Output) {
+    match value.parse::<T>() {
+        Ok(v) if (min..=max).contains(&v) => *param = v,
+        _ => output.send_string(&format!("Invalid value: {}", value))
+    }
+}
+
+/// Converts our score into a UCI score
+fn score_attribute(score :Score) -> UciInfoAttribute {
+    match score.mate_moves() {
+        Some(moves) => UciInfoAttribute::from_mate(moves.clamp(i8::MIN as i32, i8::MAX as i32) as i8),
+        None => UciInfoAttribute::from_centipawns(score.raw())
+    }
+}
+
+fn to_uci_move(mv :&Move) -> UciMove {
+    let to_square = |sq :shakmaty::Square| UciSquare::from(sq.file().char(), sq.rank().char().to_digit(10).unwrap() as u8);
+
+    match ShakmatyUci::from_standard(mv) {
+        ShakmatyUci::Normal { from, to, promotion } => UciMove {
+            from: to_square(from),
+            to: to_square(to),
+            promotion: promotion.map(|role| match role {
+                Role::Pawn => UciPiece::Pawn,
+                Role::Knight => UciPiece::Knight,
+                Role::Bishop => UciPiece::Bishop,
+                Role::Rook => UciPiece::Rook,
+                Role::Queen => UciPiece::Queen,
+                Role::King => UciPiece::King
+            })
+        },
+        ShakmatyUci::Put { .. } | ShakmatyUci::Null => panic!("Unexpected move: {}", mv)
+    }
+}
+
+fn to_std(duration :vampirc_uci::Duration) -> Duration {
+    Duration::from_millis(duration.num_milliseconds().max(0) as u64)
+}
+
+/// Where our responses go: STDOUT for a GUI, or a channel the tests read them from
+#[derive(Clone)]
+enum Output {
+    Stdout,
+    #[cfg(test)]
+    Channel(Sender<String>)
+}
+
+impl Output {
+    fn send_line(&self, line :&str) {
+        match self {
+            Output::Stdout => {
+                let stdout = io::stdout();
+                let mut stdout = stdout.lock();
+
+                writeln!(stdout, "{}", line).expect("Error writing");
+                stdout.flush().expect("Error flushing");
+            },
+            #[cfg(test)]
+            Output::Channel(sender) => {
+                // the test may have stopped listening
+                let _ = sender.send(line.to_string());
+            }
+        }
+    }
+
+    fn send(&self, message :UciMessage) {
+        self.send_line(&message.to_string());
+    }
+
+    fn send_string(&self, s :&str) {
+        self.send(UciMessage::Info(vec![UciInfoAttribute::String(s.to_string())]));
+    }
+}
+
+
+#[cfg(test)]
+mod uci_tests {
+    use std::sync::mpsc::{channel, Receiver};
+    use std::thread;
+    use std::time::Duration;
+
+    use shakmaty::uci::Uci;
+
+    use crate::uci::{UciEngine, Output};
+
+    /// An engine whose responses we can read
+    fn engine() -> (UciEngine, Receiver<String>) {
+        let (sender, receiver) = channel();
+        let mut engine = UciEngine::new();
+
+        engine.output = Output::Channel(sender);
+
+        (engine, receiver)
+    }
+
+    /// Reads responses until one starts with the prefix, and returns the rest of it
+    fn wait_for(receiver :&Receiver<String>, prefix :&str) -> String {
+        loop {
+            let line = receiver.recv_timeout(Duration::from_secs(30)).unwrap_or_else(|_| panic!("No {}", prefix));
+
+            if let Some(rest) = line.strip_prefix(prefix) {
+                return rest.to_string();
+            }
+        }
+    }
+
+    /// Waits for the best move, which has to be legal in the engine's position
+    fn assert_legal_best_move(engine :&UciEngine, receiver :&Receiver<String>) {
+        let best_move = wait_for(receiver, "bestmove ");
+        let uci :Uci = best_move.split_whitespace().next().unwrap().parse().unwrap();
+
+        assert!(uci.to_move(&engine.game).is_ok(), "Illegal best move: {}", best_move);
+    }
+
+    #[test]
+    fn protocol_test() {
+        let (mut engine, receiver) = engine();
+
+        assert!(engine.handle("uci"));
+        wait_for(&receiver, "uciok");
+        assert!(engine.handle("isready"));
+        wait_for(&receiver, "readyok");
+
+        assert!(engine.handle("position startpos moves e2e4 e7e5"));
+        assert!(engine.handle("go depth 3"));
+
+        let info = wait_for(&receiver, "info depth 3 ");
+        assert!(info.starts_with("seldepth ") && info.contains(" tbhits 0 "), "{}", info);
+        assert!(wait_for(&receiver, "info string ").starts_with("qnodes "));
+        assert_legal_best_move(&engine, &receiver);
+
+        // no legal moves, after fool's mate
+        assert!(engine.handle("position startpos moves f2f3 e7e5 g2g4 d8h4"));
+        assert!(engine.handle("go depth 3"));
+        assert_eq!(wait_for(&receiver, "bestmove "), "0000");
+
+        assert!(!engine.handle("quit"));
+    }
+
+    #[test]
+    fn ponder_test() {
+        let (mut engine, receiver) = engine();
+
+        // while pondering there's no best move until the GUI says so
+        assert!(engine.handle("position startpos moves e2e4"));
+        assert!(engine.handle("go ponder wtime 1000 btime 1000"));
+        thread::sleep(Duration::from_millis(200));
+        assert!(receiver.try_iter().all(|line| !line.starts_with("bestmove")));
+
+        // ponderhit switches to the clock, and only the search with the clock reports a move
+        assert!(engine.handle("ponderhit"));
+        assert_legal_best_move(&engine, &receiver);
+        engine.stop_search();
+        assert!(receiver.try_iter().all(|line| !line.starts_with("bestmove")));
+
+        // stop ends pondering with a move too
+        assert!(engine.handle("go ponder wtime 1000 btime 1000"));
+        thread::sleep(Duration::from_millis(200));
+        assert!(engine.handle("stop"));
+        assert_legal_best_move(&engine, &receiver);
+    }
+
+    #[test]
+    fn evaluator_test() {
+        let mut engine = UciEngine::new();
+
+        // the learned evaluators need a weights file, without one we keep the evaluator we have
+        assert!(engine.set_evaluator("nn", None).is_err());
+        assert_eq!((engine.evaluator_name.as_str(), engine.evaluator_file.as_deref()), ("pst", None));
+        assert!(engine.set_evaluator("linear", Some("/nonexistent/linear.weights")).is_err());
+        assert_eq!((engine.evaluator_name.as_str(), engine.evaluator_file.as_deref()), ("pst", None));
+
+        // the GUI's choice is remembered until its file comes
+        engine.set_option("Evaluator", Some("nn".to_string()));
+        assert_eq!((engine.evaluator_name.as_str(), engine.requested_evaluator.0.as_str()), ("pst", "nn"));
+
+        engine.set_option("Evaluator", Some("PST".to_string()));
+        assert_eq!(engine.evaluator_name, "pst");
+        assert!(engine.set_evaluator("mystery", None).is_err());
+        assert_eq!(engine.evaluator_name, "pst");
+    }
+}
@@ -0,0 +1,238 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use shakmaty::{Chess, Setup, Color, Move, CastlingMode};
+use shakmaty::fen;
+use shakmaty::uci::Uci;
+use vampirc_uci::{UciMessage, UciInfoAttribute, parse_one};
+
+use crate::score::Score;
+use crate::selfplay::Player;
+
+/// How long an engine gets to start up, or to answer isready
+const STARTUP_TIMEOUT :Duration = Duration::from_secs(10);
+
+/// An external engine we play games against over UCI, like the analysis client in utils but with clocks:
+/// every read has a deadline, so an engine that hangs loses on time instead of hanging the match
+pub struct UciClient {
+    name: String,
+    child: Child,
+    stdin: ChildStdin,
+    /// The engine's output a line at a time, read on its own thread so we can stop waiting for it
+    lines: Receiver<String>,
+}
+
+impl UciClient {
+    /// Starts the engine, command is the binary and its arguments separated by spaces
+    ///
+    /// The engine's id name is used unless a name is given.
+    pub fn start(command :&str, name :Option<&str>) -> Result<UciClient, String> {
+        let mut args = command.split_whitespace();
+        let program = args.next().ok_or("Empty engine command".to_string())?;
+
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Error starting {}: {}", command, e))?;
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let (sender, lines) = channel();
+
+        // the thread ends when the engine closes its output, which disconnects the channel
+        thread::spawn(move || {
+            for line in stdout.lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut client = UciClient { name: name.unwrap_or(program).to_string(), child, stdin, lines };
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+
+        client.send("uci")?;
+
+        // engines often print a banner first, so we skip anything that isn't UCI
+        loop {
+            match parse_one(&client.recv(deadline)?) {
+                UciMessage::Id { name: Some(id), .. } if name.is_none() => client.name = id,
+                UciMessage::UciOk => break,
+                _ => ()
+            }
+        }
+
+        client.ready()?;
+
+        Ok(client)
+    }
+
+    pub fn set_option(&mut self, name :&str, value :&str) -> Result<(), String> {
+        self.send(&format!("setoption name {} value {}", name, value))?;
+        self.ready()
+    }
+
+    pub fn new_game(&mut self) -> Result<(), String> {
+        self.send("ucinewgame")?;
+        self.ready()
+    }
+
+    /// Asks for a move in game, which is start after moves, and waits at most timeout for it
+    ///
+    /// The score is from the side to move's point of view, it's the last one the engine reported.
+    #[allow(clippy::too_many_arguments)]
+    pub fn go(&mut self, game :&Chess, start :&Chess, moves :&[Move], clocks :[Duration; 2], increment :Duration, timeout :Duration) -> Result<(Move, Option<Score>), String> {
+        let deadline = Instant::now() + timeout;
+        let moves = moves.iter().map(|mv| mv.to_uci(CastlingMode::Standard).to_string()).collect::<Vec<_>>();
+        let start = fen::fen(start);
+
+        let mut position = if start == fen::fen(&Chess::default()) {
+            "position startpos".to_string()
+        } else {
+            format!("position fen {}", start)
+        };
+
+        if !moves.is_empty() {
+            position = format!("{} moves {}", position, moves.join(" "));
+        }
+
+        self.send(&position)?;
+        self.send(&format!("go wtime {} btime {} winc {} binc {}", clocks[0].as_millis(), clocks[1].as_millis(), increment.as_millis(), increment.as_millis()))?;
+
+        let mut score = None;
+
+        loop {
+            let line = self.recv(deadline).map_err(|e| if e == "timeout" { "no move in time".to_string() } else { e })?;
+
+            match parse_one(&line) {
+                UciMessage::Info(attributes) => {
+                    for attribute in attributes {
+                        if let UciInfoAttribute::Score { cp, mate, .. } = attribute {
+                            score = match (cp, mate) {
+                                (_, Some(mate)) if mate > 0 => Some(Score::mate_in(2 * mate as usize - 1)),
+                                (_, Some(mate)) => Some(Score::mated_in(2 * mate.unsigned_abs() as usize)),
+                                (Some(cp), None) => Some(Score::cp(cp as i64)),
+                                (None, None) => score
+                            };
+                        }
+                    }
+                },
+                UciMessage::BestMove { .. } => {
+                    // the move is checked against the position ourselves, as the parser accepts any squares
+                    let text = line.split_whitespace().nth(1).unwrap_or("");
+                    let mv = text.parse::<Uci>().ok()
+                        .and_then(|uci| uci.to_move(game).ok())
+                        .ok_or(format!("illegal move {}", text))?;
+
+                    return Ok((mv, score));
+                },
+                _ => ()
+            }
+        }
+    }
+
+    /// Waits for readyok, which the engine only sends once it has dealt with everything before isready
+    fn ready(&mut self) -> Result<(), String> {
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+
+        self.send("isready")?;
+
+        while parse_one(&self.recv(deadline)?) != UciMessage::ReadyOk {}
+
+        Ok(())
+    }
+
+    fn send(&mut self, line :&str) -> Result<(), String> {
+        writeln!(self.stdin, "{}", line).and_then(|_| self.stdin.flush()).map_err(|e| format!("Error writing to {}: {}", self.name, e))
+    }
+
+    /// The next line of output, the error is "timeout" once the deadline passes
+    fn recv(&self, deadline :Instant) -> Result<String, String> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+
+        self.lines.recv_timeout(timeout).map_err(|e| match e {
+            RecvTimeoutError::Timeout => "timeout".to_string(),
+            RecvTimeoutError::Disconnected => format!("{} exited", self.name)
+        })
+    }
+}
+
+impl Player for UciClient {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// We wait a little longer than the clock, so an engine that's just too slow loses on time rather than by not answering
+    fn play(&mut self, game :&Chess, start :&Chess, moves :&[Move], _history :&[u64], clocks :[Duration; 2], increment :Duration) -> Result<(Move, Option<Score>), String> {
+        let clock = clocks[if game.turn() == Color::White { 0 } else { 1 }];
+
+        self.go(game, start, moves, clocks, increment, clock + Duration::from_millis(100))
+    }
+}
+
+/// Engines get a moment to quit on their own before they're killed
+impl Drop for UciClient {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let deadline = Instant::now() + Duration::from_millis(200);
+
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+
+#[cfg(test)]
+mod uci_client_tests {
+    use std::time::Duration;
+
+    use shakmaty::Chess;
+
+    use crate::cli::parse_position;
+    use crate::score::Score;
+    use crate::uci_client::UciClient;
+
+    /// The mock engine answers every go with the move it's given
+    fn mock(mv :&str) -> String {
+        format!("sh {}/scripts/mock_uci.sh {}", env!("CARGO_MANIFEST_DIR"), mv)
+    }
+
+    #[test]
+    fn client_test() {
+        let clocks = [Duration::from_secs(1); 2];
+        let mut client = UciClient::start(&mock("e2e4"), None).unwrap();
+
+        assert_eq!(client.name, "mock");
+        assert!(client.new_game().is_ok());
+        assert!(client.set_option("Hash", "16").is_ok());
+
+        let (mv, score) = client.go(&Chess::default(), &Chess::default(), &[], clocks, Duration::ZERO, Duration::from_secs(1)).unwrap();
+
+        assert_eq!(mv.to_string(), "e2-e4");
+        assert_eq!(score, Some(Score::cp(0)));
+
+        // e2e4 isn't legal once the pawn has moved
+        let game = parse_position("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        assert_eq!(client.go(&game, &game, &[], clocks, Duration::ZERO, Duration::from_secs(1)).unwrap_err(), "illegal move e2e4");
+
+        let mut client = UciClient::start(&mock("sleep"), Some("sleepy")).unwrap();
+
+        assert_eq!(client.name, "sleepy");
+        assert_eq!(client.go(&Chess::default(), &Chess::default(), &[], clocks, Duration::ZERO, Duration::from_millis(100)).unwrap_err(), "no move in time");
+        assert!(UciClient::start("/nonexistent/engine", None).is_err());
+    }
+}
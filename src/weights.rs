@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::slice;
+
+use crate::eval::Tapered;
+
+/// The piece-square tables by role, in the order the weights file lists them
+const PST_NAMES :[&str; 6] = ["pst_pawn", "pst_knight", "pst_bishop", "pst_rook", "pst_queen", "pst_king"];
+
+/// Every number the hand written evaluation uses, so they can be loaded from a file and tuned
+///
+/// Arrays by role go pawn, knight, bishop, rook, queen, king. Arrays by rank count from the side's own
+/// back rank. The piece-square tables are from white's point of view, laid out like a board with rank 8 first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Weights {
+    pub material: [Tapered; 6],
+    pub pst: [[Tapered; 64]; 6],
+    pub tempo: Tapered,
+    pub doubled_pawn: Tapered,
+    pub isolated_pawn: Tapered,
+    pub backward_pawn: Tapered,
+    pub passed_pawn: [Tapered; 8],
+    pub mobility: [Tapered; 6],
+    pub king_attacker: [Tapered; 6],
+    pub pawn_shelter: [Tapered; 8],
+    pub pawn_storm: [Tapered; 8],
+    pub bishop_pair: Tapered,
+    pub rook_open_file: Tapered,
+    pub rook_semi_open_file: Tapered,
+    pub rook_on_seventh: Tapered,
+    pub knight_outpost: Tapered,
+}
+
+impl Weights {
+    /// Each term by the name it has in the weights file
+    pub fn terms(&self) -> Vec<(&'static str, &[Tapered])> {
+        let mut terms = vec![
+            ("material", &self.material[..]),
+            ("tempo", slice::from_ref(&self.tempo)),
+            ("doubled_pawn", slice::from_ref(&self.doubled_pawn)),
+            ("isolated_pawn", slice::from_ref(&self.isolated_pawn)),
+            ("backward_pawn", slice::from_ref(&self.backward_pawn)),
+            ("passed_pawn", &self.passed_pawn[..]),
+            ("mobility", &self.mobility[..]),
+            ("king_attacker", &self.king_attacker[..]),
+            ("pawn_shelter", &self.pawn_shelter[..]),
+            ("pawn_storm", &self.pawn_storm[..]),
+            ("bishop_pair", slice::from_ref(&self.bishop_pair)),
+            ("rook_open_file", slice::from_ref(&self.rook_open_file)),
+            ("rook_semi_open_file", slice::from_ref(&self.rook_semi_open_file)),
+            ("rook_on_seventh", slice::from_ref(&self.rook_on_seventh)),
+            ("knight_outpost", slice::from_ref(&self.knight_outpost)),
+        ];
+
+        terms.extend(PST_NAMES.iter().zip(self.pst.iter()).map(|(name, table)| (*name, &table[..])));
+
+        terms
+    }
+
+    /// Same as terms, but the values can be changed
+    pub fn terms_mut(&mut self) -> Vec<(&'static str, &mut [Tapered])> {
+        let mut terms = vec![
+            ("material", &mut self.material[..]),
+            ("tempo", slice::from_mut(&mut self.tempo)),
+            ("doubled_pawn", slice::from_mut(&mut self.doubled_pawn)),
+            ("isolated_pawn", slice::from_mut(&mut self.isolated_pawn)),
+            ("backward_pawn", slice::from_mut(&mut self.backward_pawn)),
+            ("passed_pawn", &mut self.passed_pawn[..]),
+            ("mobility", &mut self.mobility[..]),
+            ("king_attacker", &mut self.king_attacker[..]),
+            ("pawn_shelter", &mut self.pawn_shelter[..]),
+            ("pawn_storm", &mut self.pawn_storm[..]),
+            ("bishop_pair", slice::from_mut(&mut self.bishop_pair)),
+            ("rook_open_file", slice::from_mut(&mut self.rook_open_file)),
+            ("rook_semi_open_file", slice::from_mut(&mut self.rook_semi_open_file)),
+            ("rook_on_seventh", slice::from_mut(&mut self.rook_on_seventh)),
+            ("knight_outpost", slice::from_mut(&mut self.knight_outpost)),
+        ];
+
+        terms.extend(PST_NAMES.iter().zip(self.pst.iter_mut()).map(|(name, table)| (*name, &mut table[..])));
+
+        terms
+    }
+
+    /// Reads weights written by to_text, terms that aren't in the text keep their value from base
+    ///
+    /// The text is a term's name followed by its middlegame/endgame pairs, # starts a comment
+    pub fn parse(text :&str, base :&Weights) -> Result<Weights, String> {
+        let mut values = HashMap::<String, Vec<Tapered>>::new();
+        let mut current = None;
+
+        for token in text.lines().flat_map(|line| line.split('#').next().unwrap().split_whitespace()) {
+            if token.starts_with(|c :char| c.is_ascii_alphabetic()) {
+                values.insert(token.to_string(), Vec::new());
+                current = Some(token.to_string());
+                continue;
+            }
+
+            let name = current.as_ref().ok_or(format!("Value before any name: {}", token))?;
+            let (mg, eg) = token.split_once('/').ok_or(format!("Expected middlegame/endgame, found: {}", token))?;
+            let parse = |v :&str| v.parse::<i64>().map_err(|_| format!("Invalid value for {}: {}", name, token));
+
+            values.get_mut(name).unwrap().push(Tapered(parse(mg)?, parse(eg)?));
+        }
+
+        let mut weights = base.clone();
+
+        for (name, term) in weights.terms_mut() {
+            if let Some(value) = values.remove(name) {
+                if value.len() != term.len() {
+                    return Err(format!("Expected {} values for {}, found {}", term.len(), name, value.len()));
+                }
+
+                term.copy_from_slice(&value);
+            }
+        }
+
+        match values.keys().next() {
+            Some(name) => Err(format!("Unknown term: {}", name)),
+            None => Ok(weights)
+        }
+    }
+
+    /// Reads a weights file, terms missing from the file keep their value from base
+    pub fn load(path :&str, base :&Weights) -> Result<Weights, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Error reading {}: {}", path, e))?;
+
+        Weights::parse(&text, base)
+    }
+
+    /// The weights in the format parse reads, one term per line and the piece-square tables as boards
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+
+        text.push_str("# fishermann evaluation weights, every value is a middlegame/endgame pair in centipawns\n");
+        text.push_str("# by role: pawn knight bishop rook queen king, by rank: from the side's own back rank\n");
+        text.push_str("# piece-square tables are from white's point of view, with rank 8 at the top\n");
+
+        for (name, values) in self.terms() {
+            let pair = |t :&Tapered| format!("{}/{}", t.0, t.1);
+
+            if values.len() == 64 {
+                writeln!(text, "\n{}", name).unwrap();
+
+                for row in values.chunks(8) {
+                    writeln!(text, "  {}", row.iter().map(|t| format!("{:>9}", pair(t))).collect::<Vec<_>>().join(" ")).unwrap();
+                }
+            } else {
+                writeln!(text, "{} {}", name, values.iter().map(pair).collect::<Vec<_>>().join(" ")).unwrap();
+            }
+        }
+
+        text
+    }
+}
+
+
+#[cfg(test)]
+mod weights_tests {
+    use crate::eval::{Tapered, BUILTIN_WEIGHTS};
+    use crate::weights::Weights;
+
+    #[test]
+    fn round_trip_test() {
+        let text = BUILTIN_WEIGHTS.to_text();
+
+        assert_eq!(Weights::parse(&text, &BUILTIN_WEIGHTS.clone()).unwrap(), BUILTIN_WEIGHTS);
+
+        // only what's in the file changes
+        let weights = Weights::parse("tempo 1/2 # comment\nbishop_pair 3/4", &BUILTIN_WEIGHTS).unwrap();
+
+        assert_eq!((weights.tempo, weights.bishop_pair), (Tapered(1, 2), Tapered(3, 4)));
+        assert_eq!(weights.pst, BUILTIN_WEIGHTS.pst);
+
+        assert!(Weights::parse("tempo 1/2 3/4", &BUILTIN_WEIGHTS).is_err());
+        assert!(Weights::parse("tempo 1", &BUILTIN_WEIGHTS).is_err());
+        assert!(Weights::parse("queen_value 900/900", &BUILTIN_WEIGHTS).is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use shakmaty::{Chess, Setup, Color, Piece};
+
+/// One key for every (color, role, square), plus castling, en passant, and side to move
+const NUM_PIECE_KEYS :usize = 2 * 6 * 64;
+const CASTLING_OFFSET :usize = NUM_PIECE_KEYS;
+const EP_OFFSET :usize = CASTLING_OFFSET + 64;
+const TURN_OFFSET :usize = EP_OFFSET + 8;
+const NUM_KEYS :usize = TURN_OFFSET + 1;
+
+/// The keys are generated at compile time with splitmix64, so hashes are stable across runs
+const KEYS :[u64; NUM_KEYS] = generate_keys(0x5EED_F15B_E4A7_7E55);
+
+const fn generate_keys(seed :u64) -> [u64; NUM_KEYS] {
+    let mut keys = [0_u64; NUM_KEYS];
+    let mut state = seed;
+    let mut i = 0;
+
+    while i < NUM_KEYS {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        keys[i] = z ^ (z >> 31);
+
+        i += 1;
+    }
+
+    keys
+}
+
+#[inline]
+fn piece_key(square :usize, piece :&Piece) -> u64 {
+    KEYS[(piece.color as usize * 6 + (piece.role as usize - 1)) * 64 + square]
+}
+
+/// Computes the Zobrist hash of a game
+pub fn hash(game :&Chess) -> u64 {
+    let mut key = 0_u64;
+
+    for (square, piece) in game.board().pieces() {
+        key ^= piece_key(square as usize, &piece);
+    }
+
+    // castling rights are tracked by the square of the rook that can still castle
+    for square in game.castling_rights() {
+        key ^= KEYS[CASTLING_OFFSET + square as usize];
+    }
+
+    // shakmaty only reports the en passant square when a capture is actually possible
+    if let Some(square) = game.ep_square() {
+        key ^= KEYS[EP_OFFSET + square.file() as usize];
+    }
+
+    if game.turn() == Color::White {
+        key ^= KEYS[TURN_OFFSET];
+    }
+
+    key
+}
+
+
+#[cfg(test)]
+mod zobrist_tests {
+    use shakmaty::{Chess, Position, Setup, CastlingMode};
+    use shakmaty::fen::Fen;
+    use shakmaty::uci::Uci;
+
+    use crate::zobrist::hash;
+
+    fn play(moves :&[&str]) -> Chess {
+        let mut game = Chess::default();
+
+        for mv in moves {
+            let mv = mv.parse::<Uci>().unwrap().to_move(&game).unwrap();
+            game.play_unchecked(&mv);
+        }
+
+        game
+    }
+
+    #[test]
+    fn transposition_test() {
+        let a = play(&["g1f3", "g8f6", "b1c3", "b8c6"]);
+        let b = play(&["b1c3", "b8c6", "g1f3", "g8f6"]);
+
+        assert_eq!(hash(&a), hash(&b));
+        assert_ne!(hash(&a), hash(&Chess::default()));
+    }
+
+    #[test]
+    fn castling_and_turn_test() {
+        // same pieces, but the king has moved so castling rights are gone
+        let a = play(&["e2e4", "e7e5", "e1e2", "e8e7", "e2e1", "e7e8"]);
+        let b = play(&["e2e4", "e7e5"]);
+
+        assert_eq!(a.board(), b.board());
+        assert_ne!(hash(&a), hash(&b));
+
+        // same pieces, different side to move
+        let c :Chess = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1".parse::<Fen>().unwrap().position(CastlingMode::Standard).unwrap();
+
+        assert_ne!(hash(&c), hash(&Chess::default()));
+
+        // moving back and forth gets us to the same hash
+        assert_eq!(hash(&play(&["g1f3", "g8f6", "f3g1", "f6g8"])), hash(&Chess::default()));
+    }
+}
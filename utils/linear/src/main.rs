@@ -1,4 +1,4 @@
-use std::io::{BufReader, BufRead};
+use std::io::{BufReader, BufRead, Write};
 use std::fs::File;
 use nalgebra::{DVector, VectorN, DMatrix, MatrixN, U65};
 use nalgebra::linalg::QR;
@@ -82,6 +82,13 @@ fn main() {
 
     println!("COMPUTED LLS");
 
+    // save the weights for the engine: fishermann --evaluator linear linear.weights
+    let mut weights_file = File::create("linear.weights").unwrap();
+
+    for weight in lls.iter() {
+        writeln!(weights_file, "{}", weight).unwrap();
+    }
+
     // println!("LLS: {}", lls);
 
     // go through each one, and compute how far off we are
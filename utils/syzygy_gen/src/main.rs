@@ -0,0 +1,441 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use shakmaty::{Bitboard, Board, CastlingMode, Chess, Color, File, FromSetup, Piece, Position, Rank, Role, Setup, Square};
+use shakmaty::fen::Fen;
+
+/*
+ * Writes the 3 piece Syzygy tables, small enough to check in for fishermann's tests:
+ * 1) Solve every position of an endgame by retrograde analysis, the DTZ in plies
+ * 2) Put the values in the order of the Syzygy index, for each side to move (and pawn file)
+ * 3) Write them with fixed length codes, which the format reads as a Huffman code with a single length
+ */
+
+const WDL_MAGIC :[u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC :[u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+/// Blocks of 256 bytes, with a sparse index entry every 1024 values
+const BLOCK_BITS :u32 = 8;
+const SPAN_BITS :u32 = 10;
+
+/// Table flags: a single value, and DTZ stored in plies
+const SINGLE_VALUE :u8 = 128;
+const PLIES :u8 = 4 | 8;
+
+/// Maps squares into the a1-d1-d4 triangle
+const TRIANGLE :[u64; 64] = [
+    6, 0, 1, 2, 2, 1, 0, 6,
+    0, 7, 3, 4, 4, 3, 7, 0,
+    1, 3, 8, 5, 5, 8, 3, 1,
+    2, 4, 5, 9, 9, 5, 4, 2,
+    2, 4, 5, 9, 9, 5, 4, 2,
+    1, 3, 8, 5, 5, 8, 3, 1,
+    0, 7, 3, 4, 4, 3, 7, 0,
+    6, 0, 1, 2, 2, 1, 0, 6,
+];
+
+/// Maps the b1-h1-h7 triangle to 0..=27
+const LOWER :[u64; 64] = [
+    28,  0,  1,  2,  3,  4,  5,  6,
+     0, 29,  7,  8,  9, 10, 11, 12,
+     1,  7, 30, 13, 14, 15, 16, 17,
+     2,  8, 13, 31, 18, 19, 20, 21,
+     3,  9, 14, 18, 32, 22, 23, 24,
+     4, 10, 15, 19, 22, 33, 25, 26,
+     5, 11, 16, 20, 23, 25, 34, 27,
+     6, 12, 17, 21, 24, 26, 27, 35,
+];
+
+/// Positions with the three unique pieces of a pawnless table
+const PAWNLESS_SIZE :u64 = 31_332;
+/// Positions for each file of the pawn: 6 ranks, then the two kings
+const PAWN_FILE_SIZE :u64 = 6 * 63 * 62;
+
+/// The value of a move's result for the side to move there: a position of this table, or the value of one in another table
+enum Child {
+    Internal(usize),
+    External(i8),
+}
+
+/// Every legal position of an endgame, with white as the side with the extra piece
+struct Solved {
+    squares: Vec<Vec<Square>>,
+    positions: Vec<Chess>,
+    /// -2, 0 or 2 for the side to move, like the Wdl of the tables
+    wdl: Vec<i8>,
+    /// Plies to a mate or zeroing move, negative when losing
+    dtz: Vec<i32>,
+}
+
+/// A unique number for a position of a few pieces
+fn key(pos :&Chess) -> u64 {
+    let board = pos.board();
+
+    board.occupied().into_iter().fold(2 | pos.turn().is_white() as u64, |key, sq| {
+        let piece = board.piece_at(sq).unwrap();
+        key << 10 | u64::from(sq) << 4 | (piece.role as u64) << 1 | piece.color.is_white() as u64
+    })
+}
+
+fn position(pieces :&[Piece], squares :&[Square], turn :Color) -> Option<Chess> {
+    let mut board = Board::empty();
+
+    for (piece, square) in pieces.iter().zip(squares) {
+        board.set_piece_at(*square, *piece, false);
+    }
+
+    let fen = Fen { board, turn, castling_rights: Bitboard::EMPTY, ..Fen::default() };
+
+    Chess::from_setup(&fen, CastlingMode::Standard).ok()
+}
+
+/// Solves the endgame, known has the values of the endgames reached by promotions
+fn solve(pieces :&[Piece], known :&HashMap<u64, i8>) -> Solved {
+    let mut squares = Vec::new();
+    let mut positions = Vec::new();
+
+    for n in 0..64u32.pow(pieces.len() as u32) {
+        let placed = (0..pieces.len()).map(|i| Square::new(n / 64u32.pow(i as u32) % 64)).collect::<Vec<_>>();
+
+        if (1..placed.len()).any(|i| placed[..i].contains(&placed[i])) {
+            continue;
+        }
+
+        for turn in [Color::White, Color::Black] {
+            if let Some(pos) = position(pieces, &placed, turn) {
+                squares.push(placed.clone());
+                positions.push(pos);
+            }
+        }
+    }
+
+    let index = positions.iter().enumerate().map(|(i, pos)| (key(pos), i)).collect::<HashMap<_, _>>();
+
+    // each move with whether it zeroes the move counter or mates
+    let children = positions.iter().map(|pos| {
+        pos.legal_moves().iter().map(|m| {
+            let after = pos.clone().play(m).unwrap();
+            let child = match index.get(&key(&after)) {
+                Some(i) => Child::Internal(*i),
+                None if after.board().occupied() == after.board().kings() => Child::External(0),
+                None => Child::External(known[&key(&after)])
+            };
+
+            (child, m.is_zeroing(), after.is_checkmate())
+        }).collect::<Vec<_>>()
+    }).collect::<Vec<_>>();
+
+    let mut wdl = positions.iter().map(|pos| {
+        if pos.is_checkmate() { Some(-2) } else if pos.is_stalemate() { Some(0) } else { None }
+    }).collect::<Vec<_>>();
+
+    loop {
+        let mut changed = false;
+
+        for i in 0..positions.len() {
+            if wdl[i].is_some() {
+                continue;
+            }
+
+            let values = children[i].iter().map(|(child, _, _)| match child {
+                Child::Internal(j) => wdl[*j],
+                Child::External(v) => Some(*v)
+            }).collect::<Vec<_>>();
+
+            if values.contains(&Some(-2)) {
+                wdl[i] = Some(2);
+                changed = true;
+            } else if values.iter().all(|v| *v == Some(2)) {
+                wdl[i] = Some(-2);
+                changed = true;
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    let wdl = wdl.into_iter().map(|v| v.unwrap_or(0)).collect::<Vec<_>>();
+    let mut dtz = vec![0; positions.len()];
+
+    // a win by a zeroing move or a mate is 1 ply away, being mated is -1
+    for i in 0..positions.len() {
+        let value = |child :&Child| match child {
+            Child::Internal(j) => wdl[*j],
+            Child::External(v) => *v
+        };
+
+        if wdl[i] == 2 && children[i].iter().any(|(child, zeroing, mate)| value(child) == -2 && (*zeroing || *mate)) {
+            dtz[i] = 1;
+        } else if wdl[i] == -2 && children[i].iter().all(|(_, zeroing, _)| *zeroing) {
+            dtz[i] = -1;
+        }
+    }
+
+    // then one ply further each time
+    for plies in 2.. {
+        let previous = dtz.clone();
+        let mut unsolved = false;
+
+        for i in 0..positions.len() {
+            if wdl[i] == 0 || dtz[i] != 0 {
+                continue;
+            }
+
+            let internal = children[i].iter().filter(|(_, zeroing, _)| !zeroing).map(|(child, _, _)| match child {
+                Child::Internal(j) => previous[*j],
+                Child::External(_) => panic!("Non-zeroing move out of the endgame")
+            }).collect::<Vec<_>>();
+
+            if wdl[i] == 2 && internal.contains(&(1 - plies)) {
+                dtz[i] = plies;
+            } else if wdl[i] == -2 && internal.iter().all(|d| *d > 0) && internal.iter().max() == Some(&(plies - 1)) {
+                dtz[i] = -plies;
+            } else {
+                unsolved = true;
+            }
+        }
+
+        if !unsolved {
+            break;
+        }
+
+        assert!(plies < 100, "Wins longer than the 50 move rule aren't supported");
+    }
+
+    Solved { squares, positions, wdl, dtz }
+}
+
+/// The subtable (pawn file) and index of a position with white to move or not flipped, like the Syzygy encoding
+fn index(squares :&[Square], pawns :bool) -> (usize, u64) {
+    let mut squares = squares.to_vec();
+    let flip = |squares :&mut Vec<Square>, f :fn(Square) -> Square| squares.iter_mut().for_each(|sq| *sq = f(*sq));
+
+    if squares[0].file() >= File::E {
+        flip(&mut squares, Square::flip_horizontal);
+    }
+
+    if pawns {
+        let mut idx = squares[0].rank() as u64 - 1;
+        let mut factor = 6;
+
+        for i in 1..squares.len() {
+            let adjust = squares[..i].iter().filter(|sq| **sq < squares[i]).count() as u64;
+
+            idx += (u64::from(squares[i]) - adjust) * factor;
+            factor *= 64 - i as u64;
+        }
+
+        return (squares[0].file() as usize, idx);
+    }
+
+    if squares[0].rank() >= Rank::Fifth {
+        flip(&mut squares, Square::flip_vertical);
+    }
+
+    for i in 0..squares.len() {
+        let (file, rank) = (squares[i].file() as u32, squares[i].rank() as u32);
+
+        if file == rank {
+            continue;
+        }
+
+        if rank > file {
+            flip(&mut squares, Square::flip_diagonal);
+        }
+
+        break;
+    }
+
+    let offdiag = |sq :Square| sq.file() as u32 != sq.rank() as u32;
+    let sq = squares.iter().map(|sq| u64::from(*sq)).collect::<Vec<_>>();
+    let adjust1 = (sq[1] > sq[0]) as u64;
+    let adjust2 = (sq[2] > sq[0]) as u64 + (sq[2] > sq[1]) as u64;
+    let rank = |i :usize| squares[i].rank() as u64;
+
+    (0, if offdiag(squares[0]) {
+        TRIANGLE[sq[0] as usize] * 63 * 62 + (sq[1] - adjust1) * 62 + sq[2] - adjust2
+    } else if offdiag(squares[1]) {
+        6 * 63 * 62 + rank(0) * 28 * 62 + LOWER[sq[1] as usize] * 62 + sq[2] - adjust2
+    } else if offdiag(squares[2]) {
+        6 * 63 * 62 + 4 * 28 * 62 + rank(0) * 7 * 28 + (rank(1) - adjust1) * 28 + LOWER[sq[2] as usize]
+    } else {
+        6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + rank(0) * 7 * 6 + (rank(1) - adjust1) * 6 + rank(2) - adjust2
+    })
+}
+
+/// One side of a table: its header, sparse index, block lengths and compressed blocks
+struct Pairs {
+    header: Vec<u8>,
+    sparse: Vec<u8>,
+    lengths: Vec<u8>,
+    data: Vec<u8>,
+}
+
+/// Compresses the values, the ones no position has are filled in with the most common value
+fn pairs(values :&[Option<u16>], flags :u8) -> Pairs {
+    let mut counts = HashMap::new();
+
+    for v in values.iter().flatten() {
+        *counts.entry(*v).or_insert(0) += 1;
+    }
+
+    let filler = counts.iter().max_by_key(|(v, count)| (**count, **v)).map(|(v, _)| *v).unwrap_or(0);
+
+    if counts.len() <= 1 {
+        return Pairs { header: vec![flags | SINGLE_VALUE, filler as u8], sparse: vec![], lengths: vec![], data: vec![] };
+    }
+
+    let mut symbols = counts.keys().copied().collect::<Vec<_>>();
+    symbols.sort_unstable();
+
+    let bits = 32 - (symbols.len() as u32 - 1).leading_zeros();
+    let block_size = 1usize << BLOCK_BITS;
+    // the reader can run 8 bytes past the last code it needs, only 4 of them past the block
+    let per_block = (block_size * 8 - 32) / bits as usize;
+    let codes = values.iter().map(|v| symbols.binary_search(&v.unwrap_or(filler)).unwrap() as u64).collect::<Vec<_>>();
+    let blocks = codes.chunks(per_block).collect::<Vec<_>>();
+
+    let mut header = vec![flags, BLOCK_BITS as u8, SPAN_BITS as u8, 0];
+    header.extend((blocks.len() as u32).to_le_bytes());
+    header.extend([bits as u8, bits as u8]);
+    header.extend(0u16.to_le_bytes());
+    header.extend((symbols.len() as u16).to_le_bytes());
+
+    for symbol in &symbols {
+        header.extend([*symbol as u8, 0xf0 | (*symbol >> 8) as u8, 0xff]);
+    }
+
+    if symbols.len() % 2 == 1 {
+        header.push(0);
+    }
+
+    let span = 1usize << SPAN_BITS;
+    let mut sparse = Vec::new();
+
+    for i in 0..values.len().div_ceil(span) {
+        let middle = i * span + span / 2;
+        let block = middle.min(values.len() - 1) / per_block;
+
+        sparse.extend((block as u32).to_le_bytes());
+        sparse.extend(((middle - block * per_block) as u16).to_le_bytes());
+    }
+
+    let lengths = blocks.iter().flat_map(|block| (block.len() as u16 - 1).to_le_bytes()).collect();
+    let mut data = Vec::new();
+
+    for block in blocks {
+        let mut bytes = vec![0u8; block_size];
+
+        for (i, code) in block.iter().enumerate() {
+            for b in 0..bits as usize {
+                if code >> (bits as usize - 1 - b) & 1 == 1 {
+                    let bit = i * bits as usize + b;
+                    bytes[bit / 8] |= 0x80 >> (bit % 8);
+                }
+            }
+        }
+
+        data.extend(bytes);
+    }
+
+    Pairs { header, sparse, lengths, data }
+}
+
+/// The table file, sides holds the subtables of each pawn file in turn
+fn table(magic :[u8; 4], pieces :&[Piece], sides :Vec<Pairs>, files :usize, dtz :bool) -> Vec<u8> {
+    let pawns = pieces[0].role == Role::Pawn;
+    let mut out = magic.to_vec();
+
+    out.push(1 | if pawns { 2 } else { 0 });
+
+    // the pieces in the order they're indexed, the same for both sides
+    for _ in 0..files {
+        out.push(0);
+        out.extend(pieces.iter().map(|p| {
+            let nibble = p.role as u8 | if p.color.is_white() { 0 } else { 8 };
+            nibble << 4 | nibble
+        }));
+    }
+
+    if out.len() % 2 == 1 {
+        out.push(0);
+    }
+
+    out.extend(sides.iter().flat_map(|side| side.header.clone()));
+
+    if dtz && out.len() % 2 == 1 {
+        out.push(0);
+    }
+
+    out.extend(sides.iter().flat_map(|side| side.sparse.clone()));
+    out.extend(sides.iter().flat_map(|side| side.lengths.clone()));
+
+    for side in &sides {
+        while !out.len().is_multiple_of(64) {
+            out.push(0);
+        }
+
+        out.extend(&side.data);
+    }
+
+    // table files are 16 bytes past a multiple of 64, which leaves room for the reader to run past the last block
+    while out.len() % 64 != 16 {
+        out.push(0);
+    }
+
+    out
+}
+
+fn main() {
+    let dir = env::args().nth(1).unwrap_or_else(|| "../../data/syzygy".to_string());
+    let mut known = HashMap::new();
+
+    fs::create_dir_all(&dir).expect("Error creating directory");
+
+    // the pawn promotes into the others, so it comes last
+    for role in [Role::Queen, Role::Rook, Role::Bishop, Role::Knight, Role::Pawn] {
+        let pieces = [role.of(Color::White), Color::White.king(), Color::Black.king()];
+        let name = format!("K{}vK", role.upper_char());
+        let pawns = role == Role::Pawn;
+        let (files, size) = if pawns { (4, PAWN_FILE_SIZE) } else { (1, PAWNLESS_SIZE) };
+        let solved = solve(&pieces, &known);
+
+        // WDL for each side to move, DTZ for white to move only
+        let mut wdl = vec![vec![None; size as usize]; files * 2];
+        let mut dtz = vec![vec![None; size as usize]; files];
+
+        for (i, squares) in solved.squares.iter().enumerate() {
+            let (file, idx) = index(squares, pawns);
+            let white = solved.positions[i].turn().is_white();
+            let value = Some((solved.wdl[i] + 2) as u16);
+            let slot = &mut wdl[file * 2 + if white { 0 } else { 1 }][idx as usize];
+
+            assert!(slot.is_none() || *slot == value, "{}: positions with index {} differ", name, idx);
+            *slot = value;
+
+            if white && solved.wdl[i] == 2 {
+                let value = Some(solved.dtz[i] as u16 - 1);
+                let slot = &mut dtz[file][idx as usize];
+
+                assert!(slot.is_none() || *slot == value, "{}: positions with index {} differ", name, idx);
+                *slot = value;
+            }
+        }
+
+        let wdl_sides = wdl.iter().map(|values| pairs(values, 0)).collect();
+        let dtz_sides = dtz.iter().map(|values| pairs(values, PLIES)).collect();
+        let path = Path::new(&dir).join(&name);
+
+        fs::write(path.with_extension("rtbw"), table(WDL_MAGIC, &pieces, wdl_sides, files, false)).expect("Error writing table");
+        fs::write(path.with_extension("rtbz"), table(DTZ_MAGIC, &pieces, dtz_sides, files, true)).expect("Error writing table");
+
+        println!("{}: {} positions, {} won, longest win {} plies", name, solved.positions.len(),
+                 solved.wdl.iter().filter(|v| **v == 2).count(), solved.dtz.iter().max().unwrap());
+
+        known.extend(solved.positions.iter().zip(&solved.wdl).map(|(pos, wdl)| (key(pos), *wdl)));
+    }
+}